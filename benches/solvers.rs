@@ -1,7 +1,10 @@
 use std::{fs::File, hint::black_box, io::BufReader};
 
 use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
-use crosswords::{Crossword, CrosswordHashMap, CrosswordNeedleSearch, Solver, Trie};
+use crosswords::{
+    CompactTrie, Crossword, CrosswordAhoCorasick, CrosswordBitsets, CrosswordDawg,
+    CrosswordFmIndex, CrosswordHashMap, CrosswordNeedleSearch, SimdSolver, Solver, Trie,
+};
 
 pub fn solver_usage_benchmark(c: &mut Criterion) {
     let crossword = Crossword::parse(BufReader::new(File::open("test_64k.txt").unwrap())).unwrap();
@@ -15,6 +18,12 @@ pub fn solver_usage_benchmark(c: &mut Criterion) {
 
     let needle_solver = CrosswordNeedleSearch::new(&crossword);
     let trie_solver = Trie::new(&crossword, Some(14));
+    let compact_trie_solver = CompactTrie::new(&crossword, Some(14));
+    let aho_corasick_solver = CrosswordAhoCorasick::new(&crossword);
+    let fm_index_solver = CrosswordFmIndex::new(&crossword);
+    let dawg_solver = CrosswordDawg::new(&crossword);
+    let simd_solver = SimdSolver::new(&crossword);
+    let bitsets_solver = CrosswordBitsets::new(&crossword);
 
     // group.sample_size(20);
 
@@ -37,6 +46,22 @@ pub fn solver_usage_benchmark(c: &mut Criterion) {
         })
     });
 
+    group.bench_function("simd", |b| {
+        b.iter(|| {
+            for word in &words {
+                simd_solver.count_occurrences(word);
+            }
+        })
+    });
+
+    group.bench_function("bitsets", |b| {
+        b.iter(|| {
+            for word in &words {
+                bitsets_solver.count_occurrences(word);
+            }
+        })
+    });
+
     group.bench_function("trie", |b| {
         b.iter(|| {
             for word in &words {
@@ -45,6 +70,46 @@ pub fn solver_usage_benchmark(c: &mut Criterion) {
         })
     });
 
+    group.bench_function("compact trie", |b| {
+        b.iter(|| {
+            for word in &words {
+                compact_trie_solver.count_occurrences(word);
+            }
+        })
+    });
+
+    group.bench_function("fm-index", |b| {
+        b.iter(|| {
+            for word in &words {
+                fm_index_solver.count_occurrences(word);
+            }
+        })
+    });
+
+    group.bench_function("dawg", |b| {
+        b.iter(|| {
+            for word in &words {
+                dawg_solver.count_occurrences(word);
+            }
+        })
+    });
+
+    group.bench_function("needle batch", |b| {
+        b.iter(|| black_box(needle_solver.count_many(&words)))
+    });
+
+    group.bench_function("trie batch", |b| {
+        b.iter(|| black_box(trie_solver.count_many(&words)))
+    });
+
+    group.bench_function("compact trie batch", |b| {
+        b.iter(|| black_box(compact_trie_solver.count_many(&words)))
+    });
+
+    group.bench_function("aho-corasick batch", |b| {
+        b.iter(|| black_box(aho_corasick_solver.count_many(&words)))
+    });
+
     for i in 1..=8 {
         let hash_solver = CrosswordHashMap::<'_>::new(&crossword, i as usize);
 
@@ -71,12 +136,42 @@ pub fn solver_construction_benchmark(c: &mut Criterion) {
         })
     });
 
+    group.bench_function("bitsets", |b| {
+        b.iter(|| {
+            black_box(CrosswordBitsets::new(&crossword));
+        })
+    });
+
     group.bench_function("trie", |b| {
         b.iter(|| {
             black_box(Trie::new(&crossword, Some(14)));
         })
     });
 
+    group.bench_function("compact trie", |b| {
+        b.iter(|| {
+            black_box(CompactTrie::new(&crossword, Some(14)));
+        })
+    });
+
+    group.bench_function("aho-corasick", |b| {
+        b.iter(|| {
+            black_box(CrosswordAhoCorasick::new(&crossword));
+        })
+    });
+
+    group.bench_function("fm-index", |b| {
+        b.iter(|| {
+            black_box(CrosswordFmIndex::new(&crossword));
+        })
+    });
+
+    group.bench_function("dawg", |b| {
+        b.iter(|| {
+            black_box(CrosswordDawg::new(&crossword));
+        })
+    });
+
     for i in 1..=8 {
         group.bench_with_input(BenchmarkId::new("hash", i), &i, |b, _| {
             b.iter(|| {
@@ -88,9 +183,60 @@ pub fn solver_construction_benchmark(c: &mut Criterion) {
     group.finish();
 }
 
+#[cfg(feature = "parallel")]
+pub fn parallel_benchmark(c: &mut Criterion) {
+    use crosswords::ParallelSolver;
+
+    let crossword = Crossword::parse(BufReader::new(File::open("test_64k.txt").unwrap())).unwrap();
+
+    let words = include_str!("../words.txt")
+        .split('\n')
+        .map(|w| w.as_bytes())
+        .collect::<Vec<_>>();
+
+    let trie_solver = Trie::new(&crossword, Some(14));
+
+    let mut group = c.benchmark_group("parallel");
+
+    for threads in [1, 2, 4, 8] {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .unwrap();
+
+        group.bench_with_input(
+            BenchmarkId::new("trie construction", threads),
+            &threads,
+            |b, _| b.iter(|| pool.install(|| black_box(Trie::new(&crossword, Some(14))))),
+        );
+
+        group.bench_with_input(
+            BenchmarkId::new("hash 8 construction", threads),
+            &threads,
+            |b, _| {
+                b.iter(|| pool.install(|| black_box(CrosswordHashMap::<'_>::new(&crossword, 8))))
+            },
+        );
+
+        group.bench_with_input(BenchmarkId::new("trie batch", threads), &threads, |b, _| {
+            b.iter(|| pool.install(|| black_box(trie_solver.par_count_many(&words))))
+        });
+    }
+
+    group.finish();
+}
+
+#[cfg(not(feature = "parallel"))]
 criterion_group!(
     benches,
     solver_usage_benchmark,
     solver_construction_benchmark
 );
+#[cfg(feature = "parallel")]
+criterion_group!(
+    benches,
+    solver_usage_benchmark,
+    solver_construction_benchmark,
+    parallel_benchmark
+);
 criterion_main!(benches);
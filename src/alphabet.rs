@@ -0,0 +1,88 @@
+use crate::EstimateSize;
+
+/// Symbols used by a grid numbered densely from zero, so that a cell takes [`Alphabet::bits`] bits,
+/// e.g. 5 for Latin letters, and tries can index children by symbol in arrays of a fixed size.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Alphabet {
+    dense: Box<[Option<u8>; 256]>,
+    codes: Box<[u8]>,
+}
+
+impl EstimateSize for Alphabet {
+    fn estimate_size(&self) -> usize {
+        size_of::<Box<[Option<u8>; 256]>>()
+            + size_of::<[Option<u8>; 256]>()
+            + self.codes.estimate_size()
+    }
+}
+
+impl Alphabet {
+    pub fn new(codes: impl IntoIterator<Item = u8>) -> Self {
+        let mut dense = Box::new([None; 256]);
+
+        for code in codes {
+            dense[code as usize] = Some(0);
+        }
+
+        let codes = (0..=u8::MAX)
+            .filter(|&code| dense[code as usize].is_some())
+            .collect::<Box<[u8]>>();
+
+        for (idx, &code) in codes.iter().enumerate() {
+            dense[code as usize] = Some(idx as u8);
+        }
+
+        Self { dense, codes }
+    }
+
+    pub fn len(&self) -> usize {
+        self.codes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.codes.is_empty()
+    }
+
+    /// Bits needed to store any dense code.
+    pub fn bits(&self) -> u32 {
+        (usize::BITS - self.len().saturating_sub(1).leading_zeros()).max(1)
+    }
+
+    /// Dense code of `code`, or `None` when the symbol isn't part of the alphabet.
+    pub fn dense(&self, code: u8) -> Option<u8> {
+        self.dense[code as usize]
+    }
+
+    pub fn code(&self, dense: u8) -> u8 {
+        self.codes[dense as usize]
+    }
+
+    pub fn codes(&self) -> &[u8] {
+        &self.codes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Crossword, Normalizer};
+
+    #[test]
+    fn dense_codes() {
+        let crossword = Crossword::parse("abcdefgh\nijklmnop\nqrstuvwx\nyzABCDEF".as_bytes())
+            .unwrap()
+            .with_normalizer(Normalizer::LOOSE);
+        let alphabet = crossword.alphabet();
+
+        assert_eq!(alphabet.len(), 26);
+        assert_eq!(alphabet.bits(), 5);
+        assert_eq!(alphabet.dense(b'A'), None);
+
+        for &code in alphabet.codes() {
+            assert_eq!(alphabet.code(alphabet.dense(code).unwrap()), code);
+        }
+
+        assert_eq!(Alphabet::new(*b"aaa").bits(), 1);
+        assert_eq!(Alphabet::new(0..=u8::MAX).bits(), 8);
+    }
+}
@@ -0,0 +1,119 @@
+use crate::EstimateSize;
+
+// Codes of symbols beyond ASCII start here, so ASCII grids are stored exactly as they're read
+const FIRST_CODE: u8 = 0x80;
+
+// ASCII symbols stored from `FIRST_CODE` up like the others, as solvers keep their bytes to separate
+// lines, e.g. the delimiter of line plans and the terminator of the FM index
+const RESERVED: [char; 2] = ['\0', '.'];
+
+/// Symbols of a grid beyond ASCII, such as letters with diacritics, each stored as a single byte
+/// from `0x80` up. Cells keep taking one byte, so solvers work the same for any language, as long as
+/// a grid has no more than 128 distinct symbols stored that way, which include `.` and `\0`.
+#[derive(Clone, Default, PartialEq, Eq, Debug)]
+pub struct Charset {
+    symbols: Vec<char>,
+}
+
+impl EstimateSize for Charset {
+    fn estimate_size(&self) -> usize {
+        self.symbols.estimate_size()
+    }
+}
+
+impl Charset {
+    /// Whether `ch` is stored as its own byte in every grid, as ASCII symbols other than `.` and
+    /// `\0` are.
+    pub fn is_plain(ch: char) -> bool {
+        ch.is_ascii() && !RESERVED.contains(&ch)
+    }
+
+    pub fn code(&self, ch: char) -> Option<u8> {
+        if Self::is_plain(ch) {
+            return Some(ch as u8);
+        }
+
+        let idx = self.symbols.iter().position(|&symbol| symbol == ch)?;
+        Some(FIRST_CODE + idx as u8)
+    }
+
+    /// Code of `ch`, assigning it the next free one when it's new.
+    pub fn insert(&mut self, ch: char) -> anyhow::Result<u8> {
+        if let Some(code) = self.code(ch) {
+            return Ok(code);
+        }
+
+        anyhow::ensure!(
+            self.symbols.len() <= (u8::MAX - FIRST_CODE) as usize,
+            "too many distinct non-ASCII symbols, '{ch}' doesn't fit"
+        );
+
+        self.symbols.push(ch);
+        Ok(FIRST_CODE + (self.symbols.len() - 1) as u8)
+    }
+
+    pub fn symbol(&self, code: u8) -> char {
+        match code.checked_sub(FIRST_CODE) {
+            Some(idx) => self.symbols[idx as usize],
+            None => code as char,
+        }
+    }
+
+    /// Symbols beyond ASCII along with their codes.
+    pub fn symbols(&self) -> impl Iterator<Item = (u8, char)> + '_ {
+        (FIRST_CODE..).zip(self.symbols.iter().copied())
+    }
+
+    /// Every symbol cells may hold along with its code, plain ASCII ones first.
+    pub fn all_symbols(&self) -> impl Iterator<Item = (u8, char)> + '_ {
+        (0..FIRST_CODE)
+            .map(|code| (code, code as char))
+            .filter(|&(_, ch)| Self::is_plain(ch))
+            .chain(self.symbols())
+    }
+
+    /// Codes of `word`, or `None` when it has a symbol that isn't in the grid, so it can't be found
+    /// there either.
+    pub fn encode(&self, word: &str) -> Option<Vec<u8>> {
+        word.chars().map(|ch| self.code(ch)).collect()
+    }
+
+    pub fn decode(&self, codes: impl IntoIterator<Item = u8>) -> String {
+        codes.into_iter().map(|code| self.symbol(code)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_symbols() {
+        let mut charset = Charset::default();
+
+        let codes = "žluťoučký kůň"
+            .chars()
+            .map(|ch| charset.insert(ch).unwrap())
+            .collect::<Vec<_>>();
+
+        assert_eq!(codes[1], b'l');
+        assert_eq!(codes[0], FIRST_CODE);
+        assert_eq!(codes[3], codes[6] - 1);
+        assert_eq!(charset.decode(codes), "žluťoučký kůň");
+
+        assert_eq!(
+            charset.encode("kůň"),
+            Some(vec![b'k', FIRST_CODE + 4, FIRST_CODE + 5])
+        );
+        assert_eq!(charset.encode("äpfel"), None);
+
+        // Bytes solvers separate lines with are never taken by cells
+        let dot = charset.insert('.').unwrap();
+        assert!(dot >= FIRST_CODE);
+        assert_eq!(charset.insert('\0').unwrap(), dot + 1);
+        assert_eq!(charset.decode([dot, b'a']), ".a");
+        assert!(charset
+            .all_symbols()
+            .all(|(code, _)| code != b'.' && code != 0));
+    }
+}
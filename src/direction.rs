@@ -0,0 +1,372 @@
+use std::{
+    fmt,
+    ops::{BitOr, BitOrAssign},
+    str::FromStr,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum Direction {
+    Right,
+    Down,
+    Diagonal,
+    AntiDiagonal,
+    Left,
+    Up,
+    UpLeft,
+    UpRight,
+}
+
+impl Direction {
+    pub const ALL: [Direction; 8] = [
+        Self::Right,
+        Self::Down,
+        Self::Diagonal,
+        Self::AntiDiagonal,
+        Self::Left,
+        Self::Up,
+        Self::UpLeft,
+        Self::UpRight,
+    ];
+
+    /// Directions reading top to bottom (or left to right for rows), one per line orientation.
+    pub const FORWARD: [Direction; 4] =
+        [Self::Right, Self::Down, Self::Diagonal, Self::AntiDiagonal];
+
+    pub fn reverse(self) -> Self {
+        match self {
+            Self::Right => Self::Left,
+            Self::Down => Self::Up,
+            Self::Diagonal => Self::UpLeft,
+            Self::AntiDiagonal => Self::UpRight,
+            Self::Left => Self::Right,
+            Self::Up => Self::Down,
+            Self::UpLeft => Self::Diagonal,
+            Self::UpRight => Self::AntiDiagonal,
+        }
+    }
+
+    pub fn is_forward(self) -> bool {
+        Self::FORWARD.contains(&self)
+    }
+
+    /// The forward direction along the same line.
+    pub fn axis(self) -> Self {
+        if self.is_forward() {
+            self
+        } else {
+            self.reverse()
+        }
+    }
+
+    pub fn offset(self) -> (isize, isize) {
+        match self {
+            Self::Right => (0, 1),
+            Self::Down => (1, 0),
+            Self::Diagonal => (1, 1),
+            Self::AntiDiagonal => (1, -1),
+            Self::Left => (0, -1),
+            Self::Up => (-1, 0),
+            Self::UpLeft => (-1, -1),
+            Self::UpRight => (-1, 1),
+        }
+    }
+
+    pub fn shift_point(self, point: (usize, usize), len: usize) -> (usize, usize) {
+        let (row, col) = self.offset();
+
+        (
+            point.0.wrapping_add_signed(row * len as isize),
+            point.1.wrapping_add_signed(col * len as isize),
+        )
+    }
+
+    pub fn shift_point_bounded(
+        self,
+        point: (usize, usize),
+        len: usize,
+        bounds: (usize, usize),
+    ) -> Option<(usize, usize)> {
+        fn shift(coord: usize, step: isize, len: usize, bound: usize) -> Option<usize> {
+            let shifted = match step {
+                -1 => coord.checked_sub(len)?,
+                0 => coord,
+                _ => coord + len,
+            };
+
+            (shifted < bound).then_some(shifted)
+        }
+
+        let (row, col) = self.offset();
+
+        Some((
+            shift(point.0, row, len, bounds.0)?,
+            shift(point.1, col, len, bounds.1)?,
+        ))
+    }
+
+    /// Same as [`Direction::shift_point_bounded`], but leaving the grid on one side enters it
+    /// again on the opposite one.
+    pub fn shift_point_wrapping(
+        self,
+        point: (usize, usize),
+        len: usize,
+        bounds: (usize, usize),
+    ) -> (usize, usize) {
+        fn shift(coord: usize, step: isize, len: usize, bound: usize) -> usize {
+            match step {
+                -1 => (coord + bound - len % bound) % bound,
+                0 => coord,
+                _ => (coord + len) % bound,
+            }
+        }
+
+        let (row, col) = self.offset();
+
+        (
+            shift(point.0, row, len, bounds.0),
+            shift(point.1, col, len, bounds.1),
+        )
+    }
+}
+
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Right => "right",
+            Self::Down => "down",
+            Self::Diagonal => "diagonal",
+            Self::AntiDiagonal => "anti-diagonal",
+            Self::Left => "left",
+            Self::Up => "up",
+            Self::UpLeft => "up-left",
+            Self::UpRight => "up-right",
+        })
+    }
+}
+
+impl FromStr for Direction {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "right" => Self::Right,
+            "down" => Self::Down,
+            "diagonal" | "down-right" => Self::Diagonal,
+            "anti-diagonal" | "down-left" => Self::AntiDiagonal,
+            "left" => Self::Left,
+            "up" => Self::Up,
+            "up-left" => Self::UpLeft,
+            "up-right" => Self::UpRight,
+            _ => anyhow::bail!("unknown direction '{s}'"),
+        })
+    }
+}
+
+/// Set of directions words may be read in.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct DirectionSet(u8);
+
+impl DirectionSet {
+    pub const EMPTY: Self = Self(0);
+    pub const ALL: Self = Self::from_slice(&Direction::ALL);
+    pub const FORWARD: Self = Self::from_slice(&Direction::FORWARD);
+
+    pub const fn from_slice(dirs: &[Direction]) -> Self {
+        let mut bits = 0;
+        let mut idx = 0;
+
+        while idx < dirs.len() {
+            bits |= 1 << dirs[idx] as u8;
+            idx += 1;
+        }
+
+        Self(bits)
+    }
+
+    pub fn contains(self, dir: Direction) -> bool {
+        self.0 & (1 << dir as u8) != 0
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn iter(self) -> impl Iterator<Item = Direction> {
+        Direction::ALL
+            .into_iter()
+            .filter(move |&dir| self.contains(dir))
+    }
+
+    /// Forward directions of the lines that can be read in at least one way.
+    pub fn axes(self) -> impl Iterator<Item = Direction> {
+        Direction::FORWARD
+            .into_iter()
+            .filter(move |&axis| self.has_axis(axis))
+    }
+
+    /// Axes words of `len` letters are read along, which for single letters is only the first one,
+    /// as they occupy the same cell along every axis.
+    pub fn axes_for(self, len: usize) -> impl Iterator<Item = Direction> {
+        self.axes().take(if len == 1 { 1 } else { usize::MAX })
+    }
+
+    pub fn has_axis(self, axis: Direction) -> bool {
+        self.contains(axis) || self.contains(axis.reverse())
+    }
+
+    /// Whether lines along `axis` can be read both ways.
+    pub fn is_mirrored(self, axis: Direction) -> bool {
+        self.contains(axis) && self.contains(axis.reverse())
+    }
+
+    /// Orientations a word has to be looked up in along `axis`, `true` standing for the reversed
+    /// word. Palindromes read the same both ways, so they are only looked up once.
+    pub fn orientations(self, axis: Direction, palindrome: bool) -> impl Iterator<Item = bool> {
+        let forward = self.contains(axis);
+        let backward = self.contains(axis.reverse()) && !(palindrome && forward);
+
+        [(forward, false), (backward, true)]
+            .into_iter()
+            .filter_map(|(allowed, reversed)| allowed.then_some(reversed))
+    }
+
+    /// Direction along `axis` to read lines in, which is the forward one unless only the
+    /// reverse is allowed.
+    pub fn orient(self, axis: Direction) -> Direction {
+        if self.contains(axis) {
+            axis
+        } else {
+            axis.reverse()
+        }
+    }
+
+    /// The direction single letters are reported in, as they occupy the same cell in all of them.
+    pub fn primary(self) -> Option<Direction> {
+        self.axes().next().map(|axis| self.orient(axis))
+    }
+}
+
+impl Default for DirectionSet {
+    fn default() -> Self {
+        Self::ALL
+    }
+}
+
+impl From<Direction> for DirectionSet {
+    fn from(dir: Direction) -> Self {
+        Self::from_slice(&[dir])
+    }
+}
+
+impl FromIterator<Direction> for DirectionSet {
+    fn from_iter<T: IntoIterator<Item = Direction>>(iter: T) -> Self {
+        iter.into_iter().fold(Self::EMPTY, |set, dir| set | dir)
+    }
+}
+
+impl<T: Into<DirectionSet>> BitOr<T> for DirectionSet {
+    type Output = Self;
+
+    fn bitor(self, rhs: T) -> Self::Output {
+        Self(self.0 | rhs.into().0)
+    }
+}
+
+impl<T: Into<DirectionSet>> BitOrAssign<T> for DirectionSet {
+    fn bitor_assign(&mut self, rhs: T) {
+        self.0 |= rhs.into().0;
+    }
+}
+
+impl FromStr for DirectionSet {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "all" => Ok(Self::ALL),
+            "forward" => Ok(Self::FORWARD),
+            _ => s.split(',').map(|dir| dir.trim().parse()).collect(),
+        }
+    }
+}
+
+impl fmt::Display for DirectionSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::ALL => write!(f, "all"),
+            Self::FORWARD => write!(f, "forward"),
+            _ => {
+                for (idx, dir) in self.iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, ",")?;
+                    }
+
+                    write!(f, "{dir}")?;
+                }
+
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reverse_directions() {
+        for dir in Direction::ALL {
+            assert_eq!(dir.reverse().reverse(), dir);
+            assert_ne!(dir.is_forward(), dir.reverse().is_forward());
+
+            let (row, col) = dir.offset();
+            assert_eq!(dir.reverse().offset(), (-row, -col));
+
+            // Walking there and back again from the center of a 5x5 grid
+            let there = dir.shift_point_bounded((2, 2), 2, (5, 5)).unwrap();
+            assert_eq!(dir.reverse().shift_point(there, 2), (2, 2));
+            assert_eq!(dir.shift_point_bounded((2, 2), 3, (5, 5)), None);
+
+            // Wrapping around a 5x5 grid lands where bounded shifting would have failed
+            let wrapped = dir.shift_point_wrapping((2, 2), 3, (5, 5));
+            assert_eq!(
+                dir.reverse().shift_point_wrapping(wrapped, 3, (5, 5)),
+                (2, 2)
+            );
+            assert_eq!(dir.shift_point_wrapping((2, 2), 5, (5, 5)), (2, 2));
+        }
+    }
+
+    #[test]
+    fn direction_sets() {
+        let kids = DirectionSet::from(Direction::Right) | Direction::Down;
+
+        assert_eq!(
+            kids.iter().collect::<Vec<_>>(),
+            [Direction::Right, Direction::Down]
+        );
+        assert_eq!(
+            kids.axes().collect::<Vec<_>>(),
+            [Direction::Right, Direction::Down]
+        );
+        assert_eq!("right, down".parse::<DirectionSet>().unwrap(), kids);
+        assert_eq!(kids.to_string().parse::<DirectionSet>().unwrap(), kids);
+        assert_eq!("all".parse::<DirectionSet>().unwrap(), DirectionSet::ALL);
+        assert!("right,sideways".parse::<DirectionSet>().is_err());
+
+        let left = DirectionSet::from(Direction::Left);
+        assert_eq!(left.primary(), Some(Direction::Left));
+        assert_eq!(
+            left.orientations(Direction::Right, false)
+                .collect::<Vec<_>>(),
+            [true]
+        );
+        assert_eq!(
+            DirectionSet::ALL
+                .orientations(Direction::Down, true)
+                .collect::<Vec<_>>(),
+            [false]
+        );
+    }
+}
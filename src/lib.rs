@@ -1,61 +1,47 @@
+mod alphabet;
+mod charset;
+mod direction;
+mod normalizer;
+mod pattern;
+mod persist;
+mod shape;
 mod size;
 mod solvers;
 mod utils;
 
+pub use alphabet::Alphabet;
+pub use charset::Charset;
+pub use direction::{Direction, DirectionSet};
+pub use normalizer::{Folding, Normalizer};
+pub use pattern::{ByteClass, Pattern};
+pub use persist::IndexHeader;
+pub use shape::Shape;
 pub use size::EstimateSize;
 pub use solvers::*;
 
-use std::io::BufRead;
+use std::{io::BufRead, ops::Deref, sync::Arc};
 
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
-pub enum Direction {
-    Right,
-    Down,
-    Diagonal,
-    AntiDiagonal,
-}
-
-impl Direction {
-    pub const ALL: [Direction; 4] = [Self::Right, Self::Down, Self::Diagonal, Self::AntiDiagonal];
-
-    pub fn shift_point(self, point: (usize, usize), len: usize) -> (usize, usize) {
-        match self {
-            Self::Right => (point.0, point.1 + len),
-            Self::Down => (point.0 + len, point.1),
-            Self::Diagonal => (point.0 + len, point.1 + len),
-            Self::AntiDiagonal => (point.0 + len, point.1 - len),
-        }
-    }
-
-    pub fn shift_point_bounded(
-        self,
-        point: (usize, usize),
-        len: usize,
-        bounds: (usize, usize),
-    ) -> Option<(usize, usize)> {
-        if self == Direction::AntiDiagonal && point.1 < len {
-            return None;
-        }
-
-        let (row, col) = self.shift_point(point, len);
-
-        if row >= bounds.0 || col >= bounds.1 {
-            return None;
-        }
-
-        Some((row, col))
-    }
-}
+use smallvec::SmallVec;
 
 #[derive(Clone, PartialEq, Eq)]
 pub struct Crossword {
     pub rows: usize,
     pub data: Box<[u8]>,
+    // Whether words may continue past an edge on the opposite one, as on a torus
+    pub wrapping: bool,
+    // Symbols the bytes of `data` stand for
+    pub charset: Charset,
+    // Differences between symbols solvers ignore
+    pub normalizer: Normalizer,
 }
 
 impl EstimateSize for Crossword {
     fn estimate_size(&self) -> usize {
-        self.rows.estimate_size() + self.data.estimate_size()
+        self.rows.estimate_size()
+            + self.data.estimate_size()
+            + self.wrapping.estimate_size()
+            + self.charset.estimate_size()
+            + self.normalizer.estimate_size()
     }
 }
 
@@ -67,11 +53,63 @@ impl Crossword {
             "data length must be a multiple of rows"
         );
 
-        Self { rows, data }
+        Self {
+            rows,
+            data,
+            wrapping: false,
+            charset: Charset::default(),
+            normalizer: Normalizer::EXACT,
+        }
+    }
+
+    pub fn with_wrapping(self, wrapping: bool) -> Self {
+        Self { wrapping, ..self }
+    }
+
+    pub fn with_charset(self, charset: Charset) -> Self {
+        Self { charset, ..self }
+    }
+
+    pub fn with_normalizer(self, normalizer: Normalizer) -> Self {
+        Self { normalizer, ..self }
+    }
+
+    /// Codes `word` is looked up by in this grid, or `None` when it has a symbol that can't be
+    /// found here.
+    pub fn encode(&self, word: &str) -> Option<Vec<u8>> {
+        self.normalizer.encode(&self.charset, word)
+    }
+
+    pub fn folding(&self) -> Folding {
+        self.normalizer.folding(&self.charset)
+    }
+
+    /// Folded symbols present in the grid.
+    pub fn alphabet(&self) -> Alphabet {
+        let folding = self.folding();
+        Alphabet::new(self.data.iter().map(|&code| folding.fold(code)))
+    }
+
+    /// Fingerprint of everything solvers index, i.e. the shape, the folded symbols and wrapping,
+    /// which stays the same across runs and platforms so that saved indexes can be checked.
+    pub fn grid_hash(&self) -> u64 {
+        let folding = self.folding();
+        let shape = [self.rows() as u64, self.cols() as u64, self.wrapping as u64];
+
+        shape
+            .iter()
+            .flat_map(|value| value.to_le_bytes())
+            .chain((0..=u8::MAX).map(|code| folding.fold(code)))
+            .chain(self.data.iter().copied())
+            // FNV-1a
+            .fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+                (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+            })
     }
 
     pub fn parse(reader: impl BufRead) -> anyhow::Result<Self> {
         let mut data = vec![];
+        let mut charset = Charset::default();
 
         let mut cols = 0;
 
@@ -82,18 +120,22 @@ impl Crossword {
                 continue;
             }
 
+            let len = data.len();
+
+            for ch in row.chars() {
+                data.push(charset.insert(ch)?);
+            }
+
             if cols == 0 {
-                cols = row.len();
+                cols = data.len() - len;
             }
 
-            if cols != row.len() {
+            if cols != data.len() - len {
                 anyhow::bail!("inconsistent row length");
             }
-
-            data.extend(row.as_bytes().iter().copied());
         }
 
-        Ok(Self::new(data.len() / cols, data.into_boxed_slice()))
+        Ok(Self::new(data.len() / cols, data.into_boxed_slice()).with_charset(charset))
     }
 
     pub fn rows(&self) -> usize {
@@ -104,6 +146,14 @@ impl Crossword {
         self.data.len() / self.rows
     }
 
+    pub fn shape(&self) -> Shape {
+        Shape {
+            rows: self.rows(),
+            cols: self.cols(),
+            wrapping: self.wrapping,
+        }
+    }
+
     pub fn get(&self, row: usize, col: usize) -> u8 {
         self.data[row * self.cols() + col]
     }
@@ -131,10 +181,11 @@ impl Crossword {
         dir: Direction,
         len: usize,
     ) -> Option<impl ExactSizeIterator<Item = u8> + '_> {
-        dir.shift_point_bounded((row, col), len - 1, (self.rows(), self.cols()))?;
+        let shape = self.shape();
+        shape.shift_point((row, col), dir, len.checked_sub(1)?)?;
 
         Some((0..len).map(move |i| {
-            let (row, col) = dir.shift_point((row, col), i);
+            let (row, col) = shape.shift_point_unchecked((row, col), dir, i);
             self.get(row, col)
         }))
     }
@@ -146,22 +197,188 @@ impl Crossword {
         dir: Direction,
         word: impl ExactSizeIterator<Item = u8>,
     ) -> bool {
-        if dir
-            .shift_point_bounded((row, col), word.len() - 1, (self.rows(), self.cols()))
-            .is_none()
-        {
-            return false;
-        };
+        self.replace_word(row, col, dir, word).is_some()
+    }
 
-        for (k, ch) in word.enumerate() {
-            let (row, col) = dir.shift_point((row, col), k);
-            self.data[row * self.cols() + col] = ch;
+    /// Same as [`Crossword::set_word`], returning the cells whose symbols changed, which solvers
+    /// following the grid are told about with [`UpdatableSolver::update`].
+    pub fn replace_word(
+        &mut self,
+        row: usize,
+        col: usize,
+        dir: Direction,
+        word: impl ExactSizeIterator<Item = u8>,
+    ) -> Option<Vec<CellChange>> {
+        let shape = self.shape();
+        shape.shift_point((row, col), dir, word.len().checked_sub(1)?)?;
+
+        let mut changes = vec![];
+
+        for (k, new) in word.enumerate() {
+            let (row, col) = shape.shift_point_unchecked((row, col), dir, k);
+            let old = std::mem::replace(&mut self.data[row * shape.cols + col], new);
+
+            if old != new {
+                changes.push(CellChange { row, col, old, new });
+            }
         }
 
-        true
+        Some(changes)
+    }
+}
+
+/// Grid a solver reads from, either borrowed or shared through an [`Arc`], which makes the solver
+/// `'static` so that it can be kept next to the grid, e.g. in the state of a server.
+#[derive(Clone)]
+pub enum CrosswordRef<'a> {
+    Borrowed(&'a Crossword),
+    Shared(Arc<Crossword>),
+}
+
+impl Deref for CrosswordRef<'_> {
+    type Target = Crossword;
+
+    fn deref(&self) -> &Crossword {
+        match self {
+            Self::Borrowed(crossword) => crossword,
+            Self::Shared(crossword) => crossword,
+        }
+    }
+}
+
+impl<'a> From<&'a Crossword> for CrosswordRef<'a> {
+    fn from(crossword: &'a Crossword) -> Self {
+        Self::Borrowed(crossword)
+    }
+}
+
+impl From<Arc<Crossword>> for CrosswordRef<'static> {
+    fn from(crossword: Arc<Crossword>) -> Self {
+        Self::Shared(crossword)
+    }
+}
+
+impl From<Crossword> for CrosswordRef<'static> {
+    fn from(crossword: Crossword) -> Self {
+        Self::Shared(Arc::new(crossword))
+    }
+}
+
+impl EstimateSize for CrosswordRef<'_> {
+    fn estimate_size(&self) -> usize {
+        match self {
+            // A grid held elsewhere too is counted by its other owners
+            Self::Shared(crossword) if Arc::strong_count(crossword) == 1 => {
+                size_of::<Self>() + crossword.estimate_size()
+            }
+            _ => size_of::<Self>(),
+        }
+    }
+}
+
+/// A cell of a grid whose symbol was rewritten from `old` to `new`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct CellChange {
+    pub row: usize,
+    pub col: usize,
+    pub old: u8,
+    pub new: u8,
+}
+
+/// A single occurrence of a word, which reads from `(row, col)` along `dir` for `len` cells.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct Match {
+    pub row: usize,
+    pub col: usize,
+    pub dir: Direction,
+    pub len: usize,
+}
+
+impl Match {
+    /// Occurrence covering `len` cells from `start` along `dir`, read from the last cell when
+    /// `reversed` is set.
+    pub fn from_segment(
+        shape: Shape,
+        start: (usize, usize),
+        dir: Direction,
+        len: usize,
+        reversed: bool,
+    ) -> Self {
+        let ((row, col), dir) = if reversed {
+            (
+                shape.shift_point_unchecked(start, dir, len - 1),
+                dir.reverse(),
+            )
+        } else {
+            (start, dir)
+        };
+
+        Self { row, col, dir, len }
+    }
+
+    pub fn is_reversed(&self) -> bool {
+        !self.dir.is_forward()
+    }
+
+    pub fn cells(self, shape: Shape) -> impl Iterator<Item = (usize, usize)> {
+        (0..self.len).map(move |i| shape.shift_point_unchecked((self.row, self.col), self.dir, i))
     }
 }
 
 pub trait Solver {
     fn count_occurrences(&self, word: &[u8]) -> usize;
+
+    fn find_occurrences(&self, word: &[u8]) -> Vec<Match>;
+
+    fn count_many(&self, words: &[&[u8]]) -> Vec<usize> {
+        words
+            .iter()
+            .map(|word| self.count_occurrences(word))
+            .collect()
+    }
+}
+
+/// Batched queries answered across threads, each counting a chunk of the words with
+/// [`Solver::count_many`], so solvers sharing work between words keep doing so within a chunk.
+#[cfg(feature = "parallel")]
+pub trait ParallelSolver: Solver + Sync {
+    fn par_count_many(&self, words: &[&[u8]]) -> Vec<usize> {
+        use rayon::prelude::*;
+
+        let chunk = words.len().div_ceil(rayon::current_num_threads()).max(1);
+
+        words
+            .par_chunks(chunk)
+            .flat_map_iter(|chunk| self.count_many(chunk))
+            .collect()
+    }
+}
+
+#[cfg(feature = "parallel")]
+impl<S: Solver + Sync + ?Sized> ParallelSolver for S {}
+
+/// Cells a word is spelled along, in reading order.
+pub type CellPath = SmallVec<[(usize, usize); 8]>;
+
+/// Solvers finding words along paths of adjacent cells instead of straight lines. An occurrence is
+/// a distinct path, so a palindrome read back along the same path is counted once.
+pub trait PathSolver {
+    fn count_paths(&self, word: &[u8]) -> usize;
+
+    fn find_paths(&self, word: &[u8]) -> Vec<CellPath>;
+}
+
+/// Solvers following edits of their grid, which only count the lines passing through changed cells
+/// again instead of being built from scratch.
+pub trait UpdatableSolver: Solver {
+    /// Updates the solver after `changes` were made to its grid, which `crossword` is as edited.
+    /// Solvers reading cells keep sharing `crossword` from then on.
+    fn update(&mut self, crossword: &Arc<Crossword>, changes: &[CellChange]);
+}
+
+/// Solvers also answering [`Pattern`] queries, counted with the same semantics as words.
+pub trait PatternSolver: Solver {
+    fn count_pattern(&self, pattern: &Pattern) -> usize;
+
+    fn find_pattern(&self, pattern: &Pattern) -> Vec<Match>;
 }
@@ -1,16 +1,27 @@
 use std::{
     fs::File,
-    io::{BufReader, BufWriter, Write},
+    io::{BufReader, BufWriter, Seek, Write},
     path::PathBuf,
+    sync::Arc,
 };
 
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 use crosswords::{
-    Crossword, CrosswordHashMap, CrosswordNeedleSearch, Direction, EstimateSize, NaiveSolver,
-    Solver, Trie,
+    Charset, Crossword, CrosswordHashMap, CrosswordNeedleSearch, Dictionary, DirectionSet,
+    EstimateSize, IndexHeader, Match, Normalizer, Solver, SolverSpec, StreamingCounter, Trie,
+    SOLVERS,
 };
 use rand::{distributions::Uniform, seq::SliceRandom, Rng};
 
+/// Solvers whose indexes can be saved to a file
+#[derive(Clone, Copy, ValueEnum)]
+enum IndexKind {
+    Trie,
+    Hash,
+    /// Lines of the grid, which are mapped from the file instead of being read into memory
+    Needle,
+}
+
 #[derive(Parser)]
 enum Subcommands {
     Generate {
@@ -20,49 +31,240 @@ enum Subcommands {
         #[arg(short, long)]
         cols: usize,
 
+        /// Letters to fill the grid with, which may be any Unicode symbols
+        #[arg(long, default_value = "abcdefghijklmnopqrstuvwxyz")]
+        letters: String,
+
+        /// File with one word per line to place in the grid, the bundled word list by default
+        #[arg(long)]
+        words: Option<PathBuf>,
+
+        /// Directions words may be placed in, e.g. `all`, `forward` or `right,down`
+        #[arg(short, long, default_value_t = DirectionSet::ALL)]
+        directions: DirectionSet,
+
+        /// Let words wrap around the edges of the grid
+        #[arg(long)]
+        wrap: bool,
+
         #[arg()]
         output: PathBuf,
     },
 
     Solve {
+        #[arg(long, required_unless_present_any = ["words", "regex"])]
+        word: Option<String>,
+
+        /// File with one word per line to solve all at once
+        #[arg(long, conflicts_with = "word")]
+        words: Option<PathBuf>,
+
+        /// Regular expression to match along every line, listing where it was found
+        #[arg(long, conflicts_with_all = ["word", "words"])]
+        regex: Option<String>,
+
+        /// Directions words may be read in, e.g. `all`, `forward` or `right,down`
+        #[arg(short, long, default_value_t = DirectionSet::ALL)]
+        directions: DirectionSet,
+
+        /// Let words wrap around the edges of the grid
+        #[arg(long)]
+        wrap: bool,
+
+        /// Match words regardless of letter case
+        #[arg(long)]
+        ignore_case: bool,
+
+        /// Match words regardless of diacritics, e.g. "eclair" as "éclair"
         #[arg(long)]
-        word: String,
+        ignore_diacritics: bool,
+
+        /// Index saved by the `index` subcommand to count words with, instead of building solvers
+        #[arg(long, conflicts_with = "regex")]
+        index: Option<PathBuf>,
+
+        /// Count words while reading the grid row by row, keeping no more rows than the longest
+        /// word spans, for grids too large to load
+        #[arg(long, conflicts_with_all = ["regex", "index", "wrap"])]
+        stream: bool,
+
+        /// Solver to count words with, e.g. `naive`, `trie:14` or `hash:6`, which may be given
+        /// several times
+        #[arg(
+            long = "solver",
+            value_name = "SOLVER",
+            default_values = ["naive", "needle", "hash:4"],
+            conflicts_with_all = ["regex", "index", "stream"]
+        )]
+        solvers: Vec<SolverSpec>,
+
+        #[arg()]
+        input: PathBuf,
+    },
+
+    /// Builds an index of the grid and saves it for `solve --index` to load
+    Index {
+        #[arg(long, value_enum)]
+        solver: IndexKind,
+
+        /// Word length of a hash map (4 by default), or the longest words a trie keeps
+        #[arg(long)]
+        word_len: Option<usize>,
+
+        /// Directions words may be read in, e.g. `all`, `forward` or `right,down`
+        #[arg(short, long, default_value_t = DirectionSet::ALL)]
+        directions: DirectionSet,
+
+        /// Let words wrap around the edges of the grid
+        #[arg(long)]
+        wrap: bool,
+
+        /// Match words regardless of letter case
+        #[arg(long)]
+        ignore_case: bool,
+
+        /// Match words regardless of diacritics, e.g. "eclair" as "éclair"
+        #[arg(long)]
+        ignore_diacritics: bool,
+
+        #[arg()]
+        input: PathBuf,
+
+        #[arg()]
+        output: PathBuf,
+    },
+
+    /// Lists every dictionary word present in the grid
+    Scan {
+        /// File with one word per line, the bundled word list by default
+        #[arg(long)]
+        dictionary: Option<PathBuf>,
+
+        /// Directions words may be read in, e.g. `all`, `forward` or `right,down`
+        #[arg(short, long, default_value_t = DirectionSet::ALL)]
+        directions: DirectionSet,
+
+        /// Let words wrap around the edges of the grid
+        #[arg(long)]
+        wrap: bool,
+
+        /// Match words regardless of letter case
+        #[arg(long)]
+        ignore_case: bool,
+
+        /// Match words regardless of diacritics, e.g. "eclair" as "éclair"
+        #[arg(long)]
+        ignore_diacritics: bool,
 
         #[arg()]
         input: PathBuf,
     },
 
     EstimateMemory {
+        /// Solver to estimate, e.g. `trie:14` or `hash:6`, which may be given several times. Every
+        /// solver is estimated by default
+        #[arg(long = "solver", value_name = "SOLVER")]
+        solvers: Vec<SolverSpec>,
+
         #[arg()]
         input: PathBuf,
     },
 }
 
+fn read_words(word: Option<String>, words: Option<PathBuf>) -> anyhow::Result<Vec<String>> {
+    Ok(match (word, words) {
+        (Some(word), _) => vec![word],
+        (None, Some(path)) => std::fs::read_to_string(path)?
+            .lines()
+            .map(str::trim)
+            .filter(|w| !w.is_empty())
+            .map(String::from)
+            .collect(),
+        (None, None) => unreachable!("clap requires one of the arguments"),
+    })
+}
+
+fn print_counts(name: &str, words: &[String], counts: &[usize]) {
+    if let [count] = counts {
+        println!("{name}: {count}");
+        return;
+    }
+
+    println!("{name}: {} in total", counts.iter().sum::<usize>());
+
+    for (word, count) in words.iter().zip(counts) {
+        println!("  {word}: {count}");
+    }
+}
+
+fn print_matches(crossword: &Crossword, matches: &[Match]) {
+    for m in matches {
+        let found = crossword.get_word(m.row, m.col, m.dir, m.len).unwrap();
+        println!(
+            "  {} at ({}, {}) {}",
+            crossword.charset.decode(found),
+            m.row,
+            m.col,
+            m.dir
+        );
+    }
+}
+
 fn main() -> anyhow::Result<()> {
     let args = Subcommands::parse();
 
     match args {
-        Subcommands::Generate { rows, cols, output } => {
+        Subcommands::Generate {
+            rows,
+            cols,
+            letters,
+            words,
+            directions,
+            wrap,
+            output,
+        } => {
+            anyhow::ensure!(!directions.is_empty(), "at least one direction is required");
+
+            let words = match words {
+                Some(path) => std::fs::read_to_string(path)?,
+                None => include_str!("../words.txt").to_string(),
+            };
+
+            let mut charset = Charset::default();
+
+            let letters = letters
+                .chars()
+                .map(|ch| charset.insert(ch))
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            anyhow::ensure!(!letters.is_empty(), "at least one letter is required");
+
+            let words = words
+                .lines()
+                .map(str::trim)
+                .filter(|w| !w.is_empty())
+                .map(|w| w.chars().map(|ch| charset.insert(ch)).collect())
+                .collect::<anyhow::Result<Vec<Vec<u8>>>>()?;
+
             let rng = &mut rand::thread_rng();
             let mut crosswords = crosswords::Crossword::new(
                 rows,
-                rng.sample_iter(Uniform::new(b'a', b'z' + 1))
+                rng.sample_iter(Uniform::new(0, letters.len()))
+                    .map(|idx| letters[idx])
                     .take(rows * cols)
                     .collect::<Box<[u8]>>(),
-            );
+            )
+            .with_wrapping(wrap)
+            .with_charset(charset);
 
-            let words = include_str!("../words.txt")
-                .trim()
-                .split('\n')
-                .collect::<Vec<_>>();
+            let directions = directions.iter().collect::<Vec<_>>();
 
             for word in words.choose_multiple(rng, rows + cols) {
                 loop {
-                    let dir = *Direction::ALL.choose(rng).unwrap();
+                    let dir = *directions.choose(rng).unwrap();
                     let row = rng.gen_range(0..rows);
                     let col = rng.gen_range(0..cols);
 
-                    if crosswords.set_word(row, col, dir, word.as_bytes().iter().copied()) {
+                    if crosswords.set_word(row, col, dir, word.iter().copied()) {
                         break;
                     }
                 }
@@ -75,60 +277,274 @@ fn main() -> anyhow::Result<()> {
                     writer.write_all(b"\n")?;
                 }
 
-                writer.write_all(row)?;
+                writer.write_all(crosswords.charset.decode(row.iter().copied()).as_bytes())?;
             }
         }
-        Subcommands::Solve { word, input } => {
-            let crossword = Crossword::parse(BufReader::new(File::open(input)?))?;
+        Subcommands::Solve {
+            word,
+            words,
+            regex,
+            directions,
+            wrap,
+            ignore_case,
+            ignore_diacritics,
+            index,
+            stream,
+            solvers,
+            input,
+        } => {
+            let normalizer = Normalizer {
+                fold_case: ignore_case,
+                fold_diacritics: ignore_diacritics,
+            };
 
-            println!(
-                "naive: {}",
-                NaiveSolver::new(&crossword).count_occurrences(word.as_bytes())
-            );
+            if stream {
+                let words = read_words(word, words)?;
+                let queries = words.iter().map(String::as_str).collect::<Vec<_>>();
+                let counter = StreamingCounter::new(&queries, directions, normalizer)?;
+
+                let counts = counter.count(BufReader::new(File::open(input)?))?;
+                print_counts("stream", &words, &counts);
+
+                return Ok(());
+            }
+
+            let crossword = Crossword::parse(BufReader::new(File::open(input)?))?
+                .with_wrapping(wrap)
+                .with_normalizer(normalizer);
+            let crossword = Arc::new(crossword);
+
+            if let Some(regex) = regex {
+                // Symbols are matched by the bytes they're stored and folded as, except for the
+                // escaped ones, which stand for regex syntax, and the dot, which is the other way
+                // around
+                let mut escaped = false;
+                let regex = regex
+                    .chars()
+                    .map(|ch| {
+                        let code = crossword.encode(&ch.to_string());
+                        let literal = match code.as_deref() {
+                            // Follows the escaping backslash already written
+                            Some(&[code]) if escaped && ch == '.' => format!("x{code:02X}"),
+                            Some(&[code])
+                                if !escaped
+                                    && ch != '.'
+                                    && (!ch.is_ascii() || code != ch as u8) =>
+                            {
+                                format!("\\x{code:02X}")
+                            }
+                            _ => ch.to_string(),
+                        };
+
+                        escaped = !escaped && ch == '\\';
+                        literal
+                    })
+                    .collect::<String>();
+                let regex = regex::bytes::RegexBuilder::new(&regex)
+                    .unicode(false)
+                    .build()?;
+                let needle = CrosswordNeedleSearch::with_directions(&crossword, directions);
+                let mut matches = needle.find_regex(&regex);
+                matches.sort_unstable();
+
+                println!("regex: {}", matches.len());
+
+                print_matches(&crossword, &matches);
+
+                return Ok(());
+            }
+
+            let words = read_words(word, words)?;
+            // Words with symbols missing from the grid can't be found there, so they aren't
+            // asked for
+            let queries = words
+                .iter()
+                .map(|w| crossword.encode(w))
+                .collect::<Vec<_>>();
+            let known = queries
+                .iter()
+                .flatten()
+                .map(Vec::as_slice)
+                .collect::<Vec<_>>();
+
+            let report = |name: &str, solver: &dyn Solver| {
+                let mut counts = solver.count_many(&known).into_iter();
+                let counts = queries
+                    .iter()
+                    .map(|query| query.as_ref().map_or(0, |_| counts.next().unwrap()))
+                    .collect::<Vec<_>>();
+
+                print_counts(name, &words, &counts);
+            };
+
+            if let Some(path) = index {
+                let mut reader = BufReader::new(File::open(&path)?);
+                let header = IndexHeader::read(&mut reader)?;
+                reader.rewind()?;
+
+                anyhow::ensure!(
+                    header.directions == directions,
+                    "index was built for directions {}",
+                    header.directions
+                );
+
+                let name = match header.word_len {
+                    Some(len) => format!("{}:{len}", header.solver),
+                    None => header.solver.clone(),
+                };
+
+                match header.solver.as_str() {
+                    "trie" => report(&name, &Trie::load(reader, &crossword)?),
+                    "hash" => report(&name, &CrosswordHashMap::load(reader, &*crossword)?),
+                    "needle" => report(&name, &CrosswordNeedleSearch::map(&path, &crossword)?),
+                    solver => anyhow::bail!("unknown solver '{solver}' in index"),
+                }
+
+                return Ok(());
+            }
 
-            {
-                let needle = CrosswordNeedleSearch::new(&crossword);
-                println!("needle: {}", needle.count_occurrences(word.as_bytes()));
+            // Solvers are dropped as soon as they answered, so that only one of them is in memory
+            for spec in solvers {
+                report(&spec.to_string(), &*spec.build(&crossword, directions));
             }
+        }
+        Subcommands::Index {
+            solver,
+            word_len,
+            directions,
+            wrap,
+            ignore_case,
+            ignore_diacritics,
+            input,
+            output,
+        } => {
+            let crossword = Crossword::parse(BufReader::new(File::open(input)?))?
+                .with_wrapping(wrap)
+                .with_normalizer(Normalizer {
+                    fold_case: ignore_case,
+                    fold_diacritics: ignore_diacritics,
+                });
+
+            let mut writer = BufWriter::new(File::create(output)?);
 
-            {
-                let hash = CrosswordHashMap::<'_>::new(&crossword, 4);
-                println!("hash4: {}", hash.count_occurrences(word.as_bytes()));
+            match solver {
+                IndexKind::Trie => {
+                    Trie::with_directions(&crossword, word_len, directions).save(&mut writer)?
+                }
+                IndexKind::Hash => {
+                    let word_len = word_len.unwrap_or(4);
+                    anyhow::ensure!(word_len > 0, "word length must be positive");
+
+                    CrosswordHashMap::with_directions(&crossword, word_len, directions)
+                        .save(&mut writer)?
+                }
+                IndexKind::Needle => CrosswordNeedleSearch::with_directions(&crossword, directions)
+                    .save(&mut writer)?,
             }
+
+            writer.flush()?;
         }
-        Subcommands::EstimateMemory { input } => {
-            let crossword = Crossword::parse(BufReader::new(File::open(input)?))?;
+        Subcommands::Scan {
+            dictionary,
+            directions,
+            wrap,
+            ignore_case,
+            ignore_diacritics,
+            input,
+        } => {
+            let crossword = Crossword::parse(BufReader::new(File::open(input)?))?
+                .with_wrapping(wrap)
+                .with_normalizer(Normalizer {
+                    fold_case: ignore_case,
+                    fold_diacritics: ignore_diacritics,
+                });
+
+            let words = match dictionary {
+                Some(path) => std::fs::read_to_string(path)?,
+                None => include_str!("../words.txt").to_string(),
+            };
+            let dictionary = Dictionary::new(
+                words
+                    .lines()
+                    .map(str::trim)
+                    .filter(|w| !w.is_empty())
+                    .filter_map(|w| crossword.encode(w)),
+            );
+
+            let mut matches = dictionary.scan(&crossword, directions);
+            matches.sort_unstable();
+
+            println!("found {} words", matches.len());
+
+            print_matches(&crossword, &matches);
+        }
+        Subcommands::EstimateMemory { solvers, input } => {
+            let crossword = Arc::new(Crossword::parse(BufReader::new(File::open(input)?))?);
+
+            fn format_size(size: usize) -> String {
+                if size < 1024 {
+                    format!("{size} B")
+                } else {
+                    format!("{:.1} KiB", size as f64 / 1024.0)
+                }
+            }
+
+            fn print_size<T: EstimateSize + ?Sized>(name: &str, obj: &T, rel_size: usize) {
+                print_sizes(
+                    name,
+                    obj.estimate_size(),
+                    obj.estimate_mapped_size(),
+                    rel_size,
+                );
+            }
+
+            fn print_sizes(name: &str, size: usize, mapped: usize, rel_size: usize) {
+                let mapped = match mapped {
+                    0 => String::new(),
+                    mapped => format!(" + {} mapped", format_size(mapped)),
+                };
 
-            fn print_size<T: EstimateSize>(name: &str, obj: &T, rel_size: usize) {
-                let size = obj.estimate_size();
                 println!(
-                    "{name}: {} ({:.1}%)",
-                    if size < 1024 {
-                        format!("{size} B")
-                    } else {
-                        format!("{:.1} KiB", size as f64 / 1024.0)
-                    },
+                    "{name}: {} ({:.1}%){mapped}",
+                    format_size(size),
                     (size as f64 / rel_size as f64 * 100.0).round()
                 );
             }
 
+            // Every solver of the registry, along with the sizes tries and hash maps are usually
+            // built with
+            let solvers = match solvers.is_empty() {
+                false => solvers,
+                true => SOLVERS
+                    .iter()
+                    .flat_map(|entry| {
+                        let params = match entry.name {
+                            "trie" | "compact-trie" => vec![Some(14), None],
+                            "hash" => (1..=16).map(Some).collect(),
+                            _ => vec![None],
+                        };
+
+                        params.into_iter().map(|param| SolverSpec {
+                            name: entry.name,
+                            param,
+                        })
+                    })
+                    .collect(),
+            };
+
             let rel_size = crossword.estimate_size();
 
-            print_size("base object", &crossword, rel_size);
-            print_size("naive solver", &NaiveSolver::new(&crossword), rel_size);
-            print_size("needle", &CrosswordNeedleSearch::new(&crossword), rel_size);
-            print_size(
-                "trie capped to 14",
-                &Trie::new(&crossword, Some(14)),
-                rel_size,
-            );
-            print_size("uncapped trie", &Trie::new(&crossword, None), rel_size);
-            for i in 1..=16 {
-                print_size(
-                    &format!("hash {i}"),
-                    &CrosswordHashMap::<'_>::new(&crossword, i),
-                    rel_size,
-                );
+            print_size("base object", &*crossword, rel_size);
+
+            for spec in solvers {
+                let name = spec.to_string();
+                print_size(&name, &*spec.build(&crossword, DirectionSet::ALL), rel_size);
+
+                // Lines of the needle solver may also be mapped from a saved index
+                if spec.name == "needle" {
+                    let (size, mapped) = CrosswordNeedleSearch::new(&crossword).estimate_mapped();
+                    print_sizes("mapped needle", size, mapped, rel_size);
+                }
             }
         }
     }
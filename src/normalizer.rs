@@ -0,0 +1,174 @@
+use std::{borrow::Cow, iter::once};
+
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+
+use crate::{ByteClass, Charset, EstimateSize, Pattern};
+
+/// Differences between symbols ignored when matching words, e.g. so that "Éclair" is found as
+/// "eclair" when both case and diacritics are folded.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash, Debug)]
+pub struct Normalizer {
+    pub fold_case: bool,
+    pub fold_diacritics: bool,
+}
+
+impl EstimateSize for Normalizer {
+    fn estimate_size(&self) -> usize {
+        size_of::<Self>()
+    }
+}
+
+impl Normalizer {
+    pub const EXACT: Self = Self {
+        fold_case: false,
+        fold_diacritics: false,
+    };
+
+    pub const LOOSE: Self = Self {
+        fold_case: true,
+        fold_diacritics: true,
+    };
+
+    pub fn normalize(self, mut ch: char) -> char {
+        if self.fold_case {
+            let mut lower = ch.to_lowercase();
+
+            // Symbols lowercased to several ones are left alone, as cells hold a single symbol
+            if let (Some(single), None) = (lower.next(), lower.next()) {
+                ch = single;
+            }
+        }
+
+        if self.fold_diacritics {
+            ch = once(ch)
+                .nfd()
+                .find(|&part| !is_combining_mark(part))
+                .unwrap_or(ch);
+        }
+
+        ch
+    }
+
+    /// Code a symbol normalizes to in `charset`, or `None` when no symbol of the charset does.
+    fn representative(self, charset: &Charset, ch: char) -> Option<u8> {
+        let ch = self.normalize(ch);
+
+        charset.code(ch).or_else(|| {
+            charset
+                .symbols()
+                .find(|&(_, symbol)| self.normalize(symbol) == ch)
+                .map(|(code, _)| code)
+        })
+    }
+
+    /// Codes of `word` in a grid with `charset`, where its symbols may differ from the grid's ones
+    /// in what's ignored.
+    pub fn encode(self, charset: &Charset, word: &str) -> Option<Vec<u8>> {
+        word.chars()
+            .map(|ch| self.representative(charset, ch))
+            .collect()
+    }
+
+    pub fn folding(self, charset: &Charset) -> Folding {
+        let mut codes = Box::new(std::array::from_fn(|code| code as u8));
+
+        if self != Self::EXACT {
+            for (code, symbol) in charset.all_symbols() {
+                if let Some(representative) = self.representative(charset, symbol) {
+                    codes[code as usize] = representative;
+                }
+            }
+        }
+
+        Folding(codes)
+    }
+}
+
+/// Code every byte of a grid is indexed as, which is the same for all symbols normalizing to the
+/// same one. Solvers fold both the grid and queries, so they agree no matter which of the equivalent
+/// symbols a query uses.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Folding(Box<[u8; 256]>);
+
+impl EstimateSize for Folding {
+    fn estimate_size(&self) -> usize {
+        size_of::<Self>() + size_of::<[u8; 256]>()
+    }
+}
+
+impl Default for Folding {
+    fn default() -> Self {
+        Normalizer::EXACT.folding(&Charset::default())
+    }
+}
+
+impl Folding {
+    pub fn fold(&self, code: u8) -> u8 {
+        self.0[code as usize]
+    }
+
+    pub fn fold_word<'w>(&self, word: &'w [u8]) -> Cow<'w, [u8]> {
+        if word.iter().all(|&code| self.fold(code) == code) {
+            Cow::Borrowed(word)
+        } else {
+            Cow::Owned(word.iter().map(|&code| self.fold(code)).collect())
+        }
+    }
+
+    pub fn fold_pattern(&self, pattern: &Pattern) -> Pattern {
+        let folded = Pattern::from_classes(pattern.classes().iter().map(|&class| {
+            let mut folded = ByteClass::NONE;
+
+            (0..=u8::MAX)
+                .filter(|&code| class.contains(code))
+                .for_each(|code| folded.insert(self.fold(code)));
+
+            folded
+        }));
+
+        match pattern.is_prefix() {
+            true => folded.prefix(),
+            false => folded,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fold_symbols() {
+        assert_eq!(Normalizer::LOOSE.normalize('É'), 'e');
+        assert_eq!(Normalizer::LOOSE.normalize('ů'), 'u');
+        assert_eq!(Normalizer::LOOSE.normalize('ł'), 'ł');
+        assert_eq!(
+            Normalizer {
+                fold_case: true,
+                fold_diacritics: false,
+            }
+            .normalize('Ž'),
+            'ž'
+        );
+
+        let mut charset = Charset::default();
+        let grid = "Éclairs"
+            .chars()
+            .map(|ch| charset.insert(ch).unwrap())
+            .collect::<Vec<_>>();
+        let folding = Normalizer::LOOSE.folding(&charset);
+
+        assert_eq!(
+            grid.iter()
+                .map(|&code| folding.fold(code))
+                .collect::<Vec<_>>(),
+            b"eclairs"
+        );
+        assert_eq!(
+            Normalizer::LOOSE.encode(&charset, "ECLÁIR"),
+            Some(b"eclair".to_vec())
+        );
+        assert_eq!(Normalizer::EXACT.encode(&charset, "éclair"), None);
+        assert_eq!(folding.fold_word(b"ECLAIR").as_ref(), b"eclair");
+    }
+}
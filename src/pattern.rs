@@ -0,0 +1,341 @@
+use std::str::FromStr;
+
+use crate::{Charset, Crossword};
+
+/// Set of bytes a single position of a [`Pattern`] accepts.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct ByteClass([u64; 4]);
+
+impl ByteClass {
+    pub const ANY: Self = Self([u64::MAX; 4]);
+    pub const NONE: Self = Self([0; 4]);
+
+    pub fn single(ch: u8) -> Self {
+        let mut class = Self::NONE;
+        class.insert(ch);
+        class
+    }
+
+    pub fn insert(&mut self, ch: u8) {
+        self.0[ch as usize / 64] |= 1 << (ch % 64);
+    }
+
+    pub fn contains(self, ch: u8) -> bool {
+        self.0[ch as usize / 64] & (1 << (ch % 64)) != 0
+    }
+
+    pub fn intersect(self, other: Self) -> Self {
+        Self(std::array::from_fn(|idx| self.0[idx] & other.0[idx]))
+    }
+
+    pub fn union(self, other: Self) -> Self {
+        Self(std::array::from_fn(|idx| self.0[idx] | other.0[idx]))
+    }
+
+    pub fn complement(self) -> Self {
+        Self(self.0.map(|bits| !bits))
+    }
+
+    pub fn is_empty(self) -> bool {
+        self == Self::NONE
+    }
+}
+
+/// Query matching words of a fixed length, where `?` stands for any letter and `[...]` for a class
+/// of letters, e.g. `s??re`, `c[aou]t` or `[^aeiou]at`. Classes may contain ranges like `[a-f]`.
+/// A trailing `*` matches words of any length starting with the rest of the pattern, e.g. `ca*`.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct Pattern {
+    classes: Box<[ByteClass]>,
+    // Whether any letters may follow the classes
+    prefix: bool,
+}
+
+impl Pattern {
+    pub fn literal(word: &[u8]) -> Self {
+        Self::from_classes(word.iter().copied().map(ByteClass::single))
+    }
+
+    pub fn from_classes(classes: impl IntoIterator<Item = ByteClass>) -> Self {
+        Self {
+            classes: classes.into_iter().collect(),
+            prefix: false,
+        }
+    }
+
+    /// Same pattern followed by `*`, matching words that start with it.
+    pub fn prefix(self) -> Self {
+        Self {
+            prefix: true,
+            ..self
+        }
+    }
+
+    pub fn is_prefix(&self) -> bool {
+        self.prefix
+    }
+
+    /// Number of classes, which is the shortest length words matching a prefix pattern have.
+    pub fn len(&self) -> usize {
+        self.classes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.classes.is_empty()
+    }
+
+    pub fn classes(&self) -> &[ByteClass] {
+        &self.classes
+    }
+
+    pub fn matches(&self, word: impl IntoIterator<Item = u8>) -> bool {
+        let mut word = word.into_iter();
+
+        self.classes
+            .iter()
+            .all(|class| word.next().is_some_and(|ch| class.contains(ch)))
+            && (self.prefix || word.next().is_none())
+    }
+
+    /// Patterns of a fixed length matching the words of up to `max_len` letters `self` matches, one
+    /// per length.
+    pub fn expand(&self, max_len: usize) -> impl Iterator<Item = Self> + '_ {
+        let lengths = match self.prefix {
+            true => self.len()..=max_len,
+            false => self.len()..=self.len(),
+        };
+
+        lengths.map(|len| {
+            let open = len - self.len();
+            Self::from_classes(
+                self.classes
+                    .iter()
+                    .copied()
+                    .chain((0..open).map(|_| ByteClass::ANY)),
+            )
+        })
+    }
+
+    /// Pattern of a fixed length matching the words it matches read backward.
+    pub fn reversed(&self) -> Self {
+        debug_assert!(!self.prefix, "prefix patterns can't be reversed");
+        Self::from_classes(self.classes.iter().rev().copied())
+    }
+
+    /// Pattern matching the words matched by both `self` and `other`, provided they're equally long
+    /// and of a fixed length.
+    pub fn intersect(&self, other: &Self) -> Option<Self> {
+        debug_assert!(
+            !self.prefix && !other.prefix,
+            "prefix patterns can't be intersected"
+        );
+
+        if self.len() != other.len() {
+            return None;
+        }
+
+        let classes = self
+            .classes
+            .iter()
+            .zip(other.classes.iter())
+            .map(|(a, b)| a.intersect(*b))
+            .collect::<Box<[_]>>();
+
+        (!classes.iter().any(|class| class.is_empty())).then(|| Self::from_classes(classes))
+    }
+}
+
+impl Pattern {
+    /// Parses `s` for `crossword`, so that its symbols stand for the codes the grid stores them as
+    /// and match the symbols the grid's normalizer doesn't tell apart from them, the way
+    /// [`Crossword::encode`] treats words. Symbols the grid doesn't have match nothing.
+    pub fn parse_for(crossword: &Crossword, s: &str) -> anyhow::Result<Self> {
+        let normalizer = crossword.normalizer;
+        let symbols = crossword.charset.all_symbols().collect::<Vec<_>>();
+
+        Self::parse_with(s, |start, end| {
+            let normalized = normalizer.normalize(start)..=normalizer.normalize(end);
+            let mut class = ByteClass::NONE;
+
+            symbols
+                .iter()
+                .filter(|&&(_, symbol)| {
+                    (start..=end).contains(&symbol)
+                        || normalized.contains(&normalizer.normalize(symbol))
+                })
+                .for_each(|&(code, _)| class.insert(code));
+
+            Ok(class)
+        })
+    }
+
+    /// Parses `s`, with `members` giving the codes of the symbols from the first one to the second
+    /// one, which are the same for a single symbol.
+    fn parse_with(
+        s: &str,
+        members: impl Fn(char, char) -> anyhow::Result<ByteClass>,
+    ) -> anyhow::Result<Self> {
+        let mut classes = vec![];
+        let mut prefix = false;
+        let mut chars = s.chars().peekable();
+
+        while let Some(ch) = chars.next() {
+            classes.push(match ch {
+                '?' => ByteClass::ANY,
+                '[' => {
+                    let mut class = ByteClass::NONE;
+                    let mut negated = false;
+                    let mut previous = None;
+                    let mut closed = false;
+                    let mut empty = true;
+
+                    while let Some(ch) = chars.next() {
+                        match ch {
+                            ']' => {
+                                closed = true;
+                                break;
+                            }
+                            '^' if empty && !negated => negated = true,
+                            '-' if previous.is_some() => {
+                                let Some(end) = chars.next().filter(|&end| end != ']') else {
+                                    anyhow::bail!("unterminated range in '{s}'");
+                                };
+
+                                let start = previous.take().unwrap();
+                                anyhow::ensure!(start <= end, "invalid range in '{s}'");
+
+                                class = class.union(members(start, end)?);
+                            }
+                            ch => {
+                                class = class.union(members(ch, ch)?);
+                                previous = Some(ch);
+                                empty = false;
+                            }
+                        }
+                    }
+
+                    anyhow::ensure!(closed, "unterminated class in '{s}'");
+                    anyhow::ensure!(!empty, "empty class in '{s}'");
+
+                    if negated {
+                        class = class.complement();
+                    }
+
+                    class
+                }
+                '*' if chars.peek().is_none() => {
+                    prefix = true;
+                    break;
+                }
+                '*' => anyhow::bail!("'*' may only end the pattern '{s}'"),
+                ']' => anyhow::bail!("unsupported character ']' in '{s}'"),
+                ch => members(ch, ch)?,
+            });
+        }
+
+        anyhow::ensure!(!classes.is_empty(), "empty pattern");
+
+        let pattern = Self::from_classes(classes);
+        Ok(if prefix { pattern.prefix() } else { pattern })
+    }
+}
+
+/// Parses patterns of ASCII letters, which are stored as themselves in every grid, see
+/// [`Charset::is_plain`]. Patterns with
+/// other symbols are parsed for their grid by [`Pattern::parse_for`].
+impl FromStr for Pattern {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse_with(s, |start, end| {
+            anyhow::ensure!(
+                Charset::is_plain(start) && Charset::is_plain(end),
+                "symbol in '{s}' that has to be parsed for its grid"
+            );
+
+            let mut class = ByteClass::NONE;
+            (start as u8..=end as u8).for_each(|ch| class.insert(ch));
+            Ok(class)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Normalizer;
+
+    #[test]
+    fn parse_patterns() {
+        let pattern = "s??re".parse::<Pattern>().unwrap();
+
+        assert_eq!(pattern.len(), 5);
+        assert!(pattern.matches(*b"score"));
+        assert!(pattern.matches(*b"share"));
+        assert!(!pattern.matches(*b"scored"));
+        assert!(!pattern.matches(*b"scare!"));
+
+        let pattern = "[^aeiou][a-c]t".parse::<Pattern>().unwrap();
+
+        assert!(pattern.matches(*b"cat"));
+        assert!(pattern.matches(*b"bbt"));
+        assert!(!pattern.matches(*b"ant"));
+        assert!(!pattern.matches(*b"cut"));
+
+        assert!("c[at".parse::<Pattern>().is_err());
+        assert!("c*t".parse::<Pattern>().is_err());
+        assert!("*".parse::<Pattern>().is_err());
+        assert!("".parse::<Pattern>().is_err());
+
+        let pattern = "ca*".parse::<Pattern>().unwrap();
+
+        assert!(pattern.is_prefix());
+        assert!(pattern.matches(*b"ca"));
+        assert!(pattern.matches(*b"cattle"));
+        assert!(!pattern.matches(*b"c"));
+        assert!(!pattern.matches(*b"act"));
+        assert_eq!(
+            pattern.expand(4).collect::<Vec<_>>(),
+            ["ca", "ca?", "ca??"].map(|p| p.parse::<Pattern>().unwrap())
+        );
+    }
+
+    #[test]
+    fn reverse_and_intersect() {
+        let pattern = "?a[bc]".parse::<Pattern>().unwrap();
+        let reversed = pattern.reversed();
+
+        assert!(reversed.matches(*b"bax"));
+        assert_eq!(
+            pattern.intersect(&reversed),
+            Some("[bc]a[bc]".parse().unwrap())
+        );
+        assert_eq!(pattern.intersect(&Pattern::literal(b"xyz")), None);
+    }
+
+    #[test]
+    fn parse_for_grid() {
+        let crossword = Crossword::parse("kůň\nžár\n".as_bytes()).unwrap();
+        let code = |ch| crossword.charset.code(ch).unwrap();
+
+        assert!("kůň".parse::<Pattern>().is_err());
+
+        let pattern = Pattern::parse_for(&crossword, "k[ůá]ň").unwrap();
+
+        assert_eq!(pattern.len(), 3);
+        assert!(pattern.matches([b'k', code('ů'), code('ň')]));
+        assert!(pattern.matches([b'k', code('á'), code('ň')]));
+        assert!(!pattern.matches([b'k', b'u', code('ň')]));
+
+        // Symbols the grid lacks match nothing, differences the normalizer ignores match
+        assert!(!Pattern::parse_for(&crossword, "ä?")
+            .unwrap()
+            .matches([b'a', b'r']));
+
+        let crossword = crossword.clone().with_normalizer(Normalizer::LOOSE);
+        let pattern = Pattern::parse_for(&crossword, "Z[a-c]*").unwrap();
+
+        assert!(pattern.matches([code('ž'), code('á'), b'r']));
+        assert!(!pattern.matches([code('ž'), b'r']));
+    }
+}
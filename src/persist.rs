@@ -0,0 +1,358 @@
+use std::io::{self, Read, Write};
+
+use ahash::HashMap;
+use fxhash::FxHashMap;
+use smallvec::SmallVec;
+
+use crate::{Alphabet, Crossword, Direction, DirectionSet};
+
+const MAGIC: [u8; 8] = *b"XWRDIDX\0";
+const VERSION: u32 = 1;
+
+/// Values stored in index files, as little-endian integers and length-prefixed sequences.
+pub(crate) trait Persist: Sized {
+    fn persist(&self, writer: &mut dyn Write) -> io::Result<()>;
+
+    fn restore(reader: &mut dyn Read) -> io::Result<Self>;
+}
+
+pub(crate) fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+macro_rules! impl_persist {
+    ( $( $x:ty ),* ) => {
+        $(
+            impl Persist for $x {
+                fn persist(&self, writer: &mut dyn Write) -> io::Result<()> {
+                    writer.write_all(&self.to_le_bytes())
+                }
+
+                fn restore(reader: &mut dyn Read) -> io::Result<Self> {
+                    let mut bytes = [0; size_of::<$x>()];
+                    reader.read_exact(&mut bytes)?;
+
+                    Ok(<$x>::from_le_bytes(bytes))
+                }
+            }
+        )*
+    };
+}
+
+impl_persist!(u8, u16, u32, u64);
+
+impl Persist for usize {
+    fn persist(&self, writer: &mut dyn Write) -> io::Result<()> {
+        (*self as u64).persist(writer)
+    }
+
+    fn restore(reader: &mut dyn Read) -> io::Result<Self> {
+        usize::try_from(u64::restore(reader)?).map_err(|_| invalid("length out of range"))
+    }
+}
+
+impl Persist for bool {
+    fn persist(&self, writer: &mut dyn Write) -> io::Result<()> {
+        (*self as u8).persist(writer)
+    }
+
+    fn restore(reader: &mut dyn Read) -> io::Result<Self> {
+        match u8::restore(reader)? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(invalid("invalid flag")),
+        }
+    }
+}
+
+impl Persist for Direction {
+    fn persist(&self, writer: &mut dyn Write) -> io::Result<()> {
+        (*self as u8).persist(writer)
+    }
+
+    fn restore(reader: &mut dyn Read) -> io::Result<Self> {
+        Direction::ALL
+            .get(u8::restore(reader)? as usize)
+            .copied()
+            .ok_or_else(|| invalid("invalid direction"))
+    }
+}
+
+impl Persist for DirectionSet {
+    fn persist(&self, writer: &mut dyn Write) -> io::Result<()> {
+        self.iter()
+            .fold(0u8, |bits, dir| bits | 1 << dir as u8)
+            .persist(writer)
+    }
+
+    fn restore(reader: &mut dyn Read) -> io::Result<Self> {
+        let bits = u8::restore(reader)?;
+
+        Ok(Direction::ALL
+            .into_iter()
+            .filter(|&dir| bits & 1 << dir as u8 != 0)
+            .collect())
+    }
+}
+
+impl Persist for Alphabet {
+    fn persist(&self, writer: &mut dyn Write) -> io::Result<()> {
+        self.codes().to_vec().persist(writer)
+    }
+
+    fn restore(reader: &mut dyn Read) -> io::Result<Self> {
+        Ok(Alphabet::new(Vec::<u8>::restore(reader)?))
+    }
+}
+
+impl<T: Persist> Persist for Option<T> {
+    fn persist(&self, writer: &mut dyn Write) -> io::Result<()> {
+        self.is_some().persist(writer)?;
+        self.iter().try_for_each(|value| value.persist(writer))
+    }
+
+    fn restore(reader: &mut dyn Read) -> io::Result<Self> {
+        match bool::restore(reader)? {
+            true => Ok(Some(T::restore(reader)?)),
+            false => Ok(None),
+        }
+    }
+}
+
+/// Restores a length-prefixed sequence one value at a time instead of reserving room for all of
+/// them upfront, as a corrupt length mustn't lead to a huge allocation before the file runs out.
+fn restore_items<T: Persist, C: Extend<T> + Default>(reader: &mut dyn Read) -> io::Result<C> {
+    let len = usize::restore(reader)?;
+    let mut items = C::default();
+
+    for _ in 0..len {
+        items.extend([T::restore(reader)?]);
+    }
+
+    Ok(items)
+}
+
+impl<T: Persist> Persist for Vec<T> {
+    fn persist(&self, writer: &mut dyn Write) -> io::Result<()> {
+        self.len().persist(writer)?;
+        self.iter().try_for_each(|value| value.persist(writer))
+    }
+
+    fn restore(reader: &mut dyn Read) -> io::Result<Self> {
+        restore_items(reader)
+    }
+}
+
+impl<T: Persist> Persist for Box<[T]> {
+    fn persist(&self, writer: &mut dyn Write) -> io::Result<()> {
+        self.len().persist(writer)?;
+        self.iter().try_for_each(|value| value.persist(writer))
+    }
+
+    fn restore(reader: &mut dyn Read) -> io::Result<Self> {
+        Ok(Vec::restore(reader)?.into_boxed_slice())
+    }
+}
+
+impl<T: Persist, const N: usize> Persist for [T; N] {
+    fn persist(&self, writer: &mut dyn Write) -> io::Result<()> {
+        self.iter().try_for_each(|value| value.persist(writer))
+    }
+
+    fn restore(reader: &mut dyn Read) -> io::Result<Self> {
+        let items = (0..N)
+            .map(|_| T::restore(reader))
+            .collect::<io::Result<Vec<_>>>()?;
+
+        match items.try_into() {
+            Ok(items) => Ok(items),
+            Err(_) => unreachable!("exactly {N} items are restored"),
+        }
+    }
+}
+
+impl<T: Persist, const N: usize> Persist for SmallVec<[T; N]> {
+    fn persist(&self, writer: &mut dyn Write) -> io::Result<()> {
+        self.len().persist(writer)?;
+        self.iter().try_for_each(|value| value.persist(writer))
+    }
+
+    fn restore(reader: &mut dyn Read) -> io::Result<Self> {
+        restore_items(reader)
+    }
+}
+
+impl<K: Persist + Eq + std::hash::Hash, V: Persist> Persist for HashMap<K, V> {
+    fn persist(&self, writer: &mut dyn Write) -> io::Result<()> {
+        self.len().persist(writer)?;
+        self.iter().try_for_each(|(key, value)| {
+            key.persist(writer)?;
+            value.persist(writer)
+        })
+    }
+
+    fn restore(reader: &mut dyn Read) -> io::Result<Self> {
+        restore_items(reader)
+    }
+}
+
+impl<K: Persist + Eq + std::hash::Hash, V: Persist> Persist for FxHashMap<K, V> {
+    fn persist(&self, writer: &mut dyn Write) -> io::Result<()> {
+        self.len().persist(writer)?;
+        self.iter().try_for_each(|(key, value)| {
+            key.persist(writer)?;
+            value.persist(writer)
+        })
+    }
+
+    fn restore(reader: &mut dyn Read) -> io::Result<Self> {
+        restore_items(reader)
+    }
+}
+
+impl<A: Persist, B: Persist> Persist for (A, B) {
+    fn persist(&self, writer: &mut dyn Write) -> io::Result<()> {
+        self.0.persist(writer)?;
+        self.1.persist(writer)
+    }
+
+    fn restore(reader: &mut dyn Read) -> io::Result<Self> {
+        Ok((A::restore(reader)?, B::restore(reader)?))
+    }
+}
+
+impl<A: Persist, B: Persist, C: Persist> Persist for (A, B, C) {
+    fn persist(&self, writer: &mut dyn Write) -> io::Result<()> {
+        self.0.persist(writer)?;
+        self.1.persist(writer)?;
+        self.2.persist(writer)
+    }
+
+    fn restore(reader: &mut dyn Read) -> io::Result<Self> {
+        Ok((
+            A::restore(reader)?,
+            B::restore(reader)?,
+            C::restore(reader)?,
+        ))
+    }
+}
+
+/// Start of an index file, telling which solver it holds and what it was built for, so that an
+/// index is never used with another grid than its own.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct IndexHeader {
+    /// Name of the solver, e.g. `trie` or `hash`
+    pub solver: String,
+    /// Length the solver was built with, i.e. the word length of a hash map or the limit of a trie
+    pub word_len: Option<usize>,
+    pub directions: DirectionSet,
+    /// [`Crossword::grid_hash`] of the grid the index was built for
+    pub grid_hash: u64,
+}
+
+impl IndexHeader {
+    pub(crate) fn new(
+        solver: &str,
+        word_len: Option<usize>,
+        directions: DirectionSet,
+        crossword: &Crossword,
+    ) -> Self {
+        Self {
+            solver: solver.into(),
+            word_len,
+            directions,
+            grid_hash: crossword.grid_hash(),
+        }
+    }
+
+    pub fn read(reader: &mut impl Read) -> anyhow::Result<Self> {
+        let mut magic = [0; MAGIC.len()];
+        reader.read_exact(&mut magic)?;
+        anyhow::ensure!(magic == MAGIC, "not an index file");
+
+        let version = u32::restore(reader)?;
+        anyhow::ensure!(version == VERSION, "unsupported index version {version}");
+
+        Ok(Self {
+            solver: String::from_utf8(Vec::restore(reader)?)?,
+            word_len: Option::restore(reader)?,
+            directions: DirectionSet::restore(reader)?,
+            grid_hash: u64::restore(reader)?,
+        })
+    }
+
+    pub fn write(&self, writer: &mut impl Write) -> io::Result<()> {
+        writer.write_all(&MAGIC)?;
+        VERSION.persist(writer)?;
+        self.solver.as_bytes().to_vec().persist(writer)?;
+        self.word_len.persist(writer)?;
+        self.directions.persist(writer)?;
+        self.grid_hash.persist(writer)
+    }
+
+    /// Reads the header of an index of `solver` and checks it was built for `crossword`.
+    pub(crate) fn read_for(
+        reader: &mut impl Read,
+        solver: &str,
+        crossword: &Crossword,
+    ) -> anyhow::Result<Self> {
+        let header = Self::read(reader)?;
+
+        anyhow::ensure!(
+            header.solver == solver,
+            "index holds a {} solver, not a {solver} one",
+            header.solver
+        );
+        anyhow::ensure!(
+            header.grid_hash == crossword.grid_hash(),
+            "index was built for another grid"
+        );
+
+        Ok(header)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip<T: Persist>(value: &T) -> T {
+        let mut bytes = vec![];
+        value.persist(&mut bytes).unwrap();
+
+        let mut reader = bytes.as_slice();
+        let restored = T::restore(&mut reader).unwrap();
+        assert!(reader.is_empty());
+
+        restored
+    }
+
+    #[test]
+    fn values_round_trip() {
+        let positions = vec![
+            (3usize, 5usize, Direction::UpLeft),
+            (0, 0, Direction::Right),
+        ];
+        let mut counts = HashMap::<SmallVec<[u8; 8]>, usize>::default();
+        counts.insert(SmallVec::from_slice(b"abc"), 7);
+        counts.insert(SmallVec::from_slice(b"x"), 1);
+
+        assert_eq!(round_trip(&positions), positions);
+        assert_eq!(round_trip(&counts), counts);
+        assert_eq!(round_trip(&Some(14usize)), Some(14));
+        assert_eq!(round_trip(&DirectionSet::FORWARD), DirectionSet::FORWARD);
+
+        let header = IndexHeader {
+            solver: "trie".into(),
+            word_len: None,
+            directions: DirectionSet::ALL,
+            grid_hash: 0x0123_4567_89ab_cdef,
+        };
+        let mut bytes = vec![];
+        header.write(&mut bytes).unwrap();
+
+        assert_eq!(IndexHeader::read(&mut bytes.as_slice()).unwrap(), header);
+        assert!(IndexHeader::read(&mut &bytes[1..]).is_err());
+        assert!(u32::restore(&mut &bytes[..3]).is_err());
+    }
+}
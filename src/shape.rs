@@ -0,0 +1,138 @@
+use crate::{
+    utils::{gcd, lcm},
+    Direction, EstimateSize,
+};
+
+/// Dimensions of a grid along with whether lines wrap around its edges, which is all that's needed
+/// to walk its cells.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Shape {
+    pub rows: usize,
+    pub cols: usize,
+    pub wrapping: bool,
+}
+
+impl EstimateSize for Shape {
+    fn estimate_size(&self) -> usize {
+        size_of::<Self>()
+    }
+}
+
+impl Shape {
+    fn bounds(self) -> (usize, usize) {
+        (self.rows, self.cols)
+    }
+
+    /// Cells visited along `dir` before a wrapping line comes back to where it started.
+    pub fn period(self, dir: Direction) -> usize {
+        match dir.offset() {
+            (0, _) => self.cols,
+            (_, 0) => self.rows,
+            _ => lcm(self.rows, self.cols),
+        }
+    }
+
+    /// Longest word that can be read along `dir`.
+    pub fn line_len(self, dir: Direction) -> usize {
+        if self.wrapping {
+            return self.period(dir);
+        }
+
+        match dir.offset() {
+            (0, _) => self.cols,
+            (_, 0) => self.rows,
+            _ => self.rows.min(self.cols),
+        }
+    }
+
+    /// Number of lines along `axis`, which for wrapping diagonals are fewer than the rows or columns
+    /// they pass through.
+    pub fn lines(self, axis: Direction) -> usize {
+        match (axis.offset(), self.wrapping) {
+            ((0, _), _) => self.rows,
+            ((_, 0), _) => self.cols,
+            (_, true) => gcd(self.rows, self.cols),
+            (_, false) => self.rows + self.cols - 1,
+        }
+    }
+
+    /// Cell `len` steps away from `point` along `dir`, if there's one. A wrapping line never
+    /// reaches back into the cells it started from, so words can't overlap themselves.
+    pub fn shift_point(
+        self,
+        point: (usize, usize),
+        dir: Direction,
+        len: usize,
+    ) -> Option<(usize, usize)> {
+        if self.wrapping {
+            (len < self.period(dir)).then(|| dir.shift_point_wrapping(point, len, self.bounds()))
+        } else {
+            dir.shift_point_bounded(point, len, self.bounds())
+        }
+    }
+
+    /// Same as [`Shape::shift_point`] for points known to be in the grid, such as the cells of a
+    /// word that was found there.
+    pub fn shift_point_unchecked(
+        self,
+        point: (usize, usize),
+        dir: Direction,
+        len: usize,
+    ) -> (usize, usize) {
+        if self.wrapping {
+            dir.shift_point_wrapping(point, len, self.bounds())
+        } else {
+            dir.shift_point(point, len)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Crossword;
+
+    #[test]
+    fn wrapping_lines() {
+        let shape = Shape {
+            rows: 4,
+            cols: 6,
+            wrapping: true,
+        };
+
+        assert_eq!(shape.period(Direction::Left), 6);
+        assert_eq!(shape.period(Direction::Up), 4);
+        assert_eq!(shape.period(Direction::Diagonal), 12);
+        assert_eq!(shape.lines(Direction::AntiDiagonal), 2);
+
+        assert_eq!(
+            shape.shift_point((0, 0), Direction::UpLeft, 3),
+            Some((1, 3))
+        );
+        assert_eq!(shape.shift_point((0, 0), Direction::Right, 5), Some((0, 5)));
+        assert_eq!(shape.shift_point((0, 0), Direction::Right, 6), None);
+
+        let bounded = Shape {
+            wrapping: false,
+            ..shape
+        };
+
+        assert_eq!(bounded.shift_point((0, 0), Direction::UpLeft, 3), None);
+        assert_eq!(bounded.line_len(Direction::Diagonal), 4);
+        assert_eq!(bounded.lines(Direction::Diagonal), 9);
+    }
+
+    #[test]
+    fn wrapping_words() {
+        let mut crossword =
+            Crossword::new(2, b"abcdef".to_vec().into_boxed_slice()).with_wrapping(true);
+
+        assert!(crossword.set_word(0, 2, Direction::Right, b"xyz".iter().copied()));
+        assert_eq!(crossword.get_row(0), b"yzx");
+
+        // Longer words would overwrite their own beginning
+        assert!(!crossword.set_word(0, 0, Direction::Right, b"wxyz".iter().copied()));
+        assert!(crossword.get_word(1, 0, Direction::UpLeft, 6).is_some());
+        assert!(crossword.get_word(1, 0, Direction::UpLeft, 7).is_none());
+    }
+}
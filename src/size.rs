@@ -3,7 +3,14 @@ use fxhash::FxHashMap;
 use smallvec::SmallVec;
 
 pub trait EstimateSize {
+    /// Bytes the object keeps resident in memory.
     fn estimate_size(&self) -> usize;
+
+    /// Bytes of files the object maps into memory, which the OS pages in as they're read and may
+    /// evict again, so they aren't counted as resident.
+    fn estimate_mapped_size(&self) -> usize {
+        0
+    }
 }
 
 impl<T: EstimateSize> EstimateSize for Vec<T> {
@@ -69,8 +76,10 @@ macro_rules! impl_estimate_size {
     };
 }
 
-impl_estimate_size!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64);
-impl_estimate_size!(crate::Direction);
+impl_estimate_size!(
+    bool, char, u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64
+);
+impl_estimate_size!(crate::Direction, crate::DirectionSet);
 
 impl<A, B> EstimateSize for (A, B)
 where
@@ -0,0 +1,126 @@
+use ahash::HashMap;
+use aho_corasick::AhoCorasick;
+use smallvec::SmallVec;
+
+use crate::{Crossword, DirectionSet, EstimateSize, Match, Solver};
+
+use super::plans::Plans;
+
+// Words each pattern counts towards in every plan, along with whether the pattern is reversed
+type Targets = Vec<[SmallVec<[(usize, bool); 1]>; 4]>;
+
+/// Solver streaming every plan once through an automaton built over all of the queried words,
+/// which pays off when whole dictionaries are solved with [`Solver::count_many`].
+pub struct CrosswordAhoCorasick {
+    plans: Plans,
+}
+
+impl EstimateSize for CrosswordAhoCorasick {
+    fn estimate_size(&self) -> usize {
+        self.plans.estimate_size()
+    }
+}
+
+impl CrosswordAhoCorasick {
+    pub fn new(crossword: &Crossword) -> Self {
+        Self::with_directions(crossword, DirectionSet::ALL)
+    }
+
+    pub fn with_directions(crossword: &Crossword, directions: DirectionSet) -> Self {
+        Self {
+            plans: Plans::new(crossword, directions),
+        }
+    }
+
+    fn automaton(&self, words: &[&[u8]]) -> (AhoCorasick, Targets) {
+        let mut ids: HashMap<SmallVec<[u8; 16]>, usize> = HashMap::default();
+        let mut patterns = vec![];
+        let mut targets: Targets = vec![];
+
+        for (idx, word) in words.iter().enumerate() {
+            if word.is_empty() {
+                continue;
+            }
+
+            let word: &[u8] = &self.plans.layout.folding().fold_word(word);
+
+            for (plan, reversed) in self.plans.layout.searches(word) {
+                let needle = if reversed {
+                    word.iter().rev().copied().collect()
+                } else {
+                    SmallVec::from_slice(word)
+                };
+
+                let id = *ids.entry(needle).or_insert_with_key(|needle| {
+                    patterns.push(needle.clone());
+                    targets.push(Default::default());
+                    patterns.len() - 1
+                });
+
+                targets[id][plan].push((idx, reversed));
+            }
+        }
+
+        let automaton = AhoCorasick::new(&patterns).expect("automaton should fit in memory");
+
+        (automaton, targets)
+    }
+}
+
+impl Solver for CrosswordAhoCorasick {
+    fn count_occurrences(&self, word: &[u8]) -> usize {
+        self.count_many(&[word])[0]
+    }
+
+    fn find_occurrences(&self, word: &[u8]) -> Vec<Match> {
+        let (automaton, targets) = self.automaton(&[word]);
+        let mut matches = vec![];
+
+        for (idx, plan) in self.plans.iter().enumerate() {
+            for found in automaton.find_overlapping_iter(plan) {
+                if !self.plans.layout.is_unique(idx, found.start(), found.len()) {
+                    continue;
+                }
+
+                for &(_, reversed) in &targets[found.pattern().as_usize()][idx] {
+                    matches.push(self.plans.layout.to_match(
+                        idx,
+                        found.start(),
+                        word.len(),
+                        reversed,
+                    ));
+                }
+            }
+        }
+
+        matches
+    }
+
+    fn count_many(&self, words: &[&[u8]]) -> Vec<usize> {
+        let (automaton, targets) = self.automaton(words);
+        let mut counts = vec![0; words.len()];
+
+        for (idx, plan) in self.plans.iter().enumerate() {
+            // Plans can be skipped entirely, e.g. when only single letters are queried
+            if targets.iter().all(|target| target[idx].is_empty()) {
+                continue;
+            }
+
+            for found in automaton.find_overlapping_iter(plan) {
+                let targets = &targets[found.pattern().as_usize()][idx];
+
+                if targets.is_empty()
+                    || !self.plans.layout.is_unique(idx, found.start(), found.len())
+                {
+                    continue;
+                }
+
+                for &(word, _) in targets {
+                    counts[word] += 1;
+                }
+            }
+        }
+
+        counts
+    }
+}
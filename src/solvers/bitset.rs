@@ -0,0 +1,234 @@
+use smallvec::SmallVec;
+
+use crate::{
+    utils::is_palindrome, Alphabet, Crossword, Direction, DirectionSet, EstimateSize, Folding,
+    Match, Shape, Solver,
+};
+
+use super::simd::shift;
+
+/// Solver keeping a bitset of the cells holding each letter of the grid, which finds where a word
+/// starts by intersecting the bitsets of its letters, each moved back by its distance from the first
+/// one. It takes a bit per cell and letter, whatever the length of the words looked up.
+pub struct CrosswordBitsets {
+    shape: Shape,
+    directions: DirectionSet,
+    folding: Folding,
+    alphabet: Alphabet,
+    words_per_row: usize,
+    // Cells of every letter row by row, with rows padded to whole words
+    bits: Box<[u64]>,
+}
+
+impl EstimateSize for CrosswordBitsets {
+    fn estimate_size(&self) -> usize {
+        self.shape.estimate_size()
+            + self.directions.estimate_size()
+            + self.folding.estimate_size()
+            + self.alphabet.estimate_size()
+            + self.words_per_row.estimate_size()
+            + self.bits.estimate_size()
+    }
+}
+
+impl CrosswordBitsets {
+    pub fn new(crossword: &Crossword) -> Self {
+        Self::with_directions(crossword, DirectionSet::ALL)
+    }
+
+    pub fn with_directions(crossword: &Crossword, directions: DirectionSet) -> Self {
+        let shape = crossword.shape();
+        let folding = crossword.folding();
+        let alphabet = crossword.alphabet();
+        let words_per_row = shape.cols.div_ceil(64);
+        let letter_len = words_per_row * shape.rows;
+        let mut bits = vec![0; letter_len * alphabet.len()].into_boxed_slice();
+
+        for (row, cells) in crossword.get_rows().enumerate() {
+            for (col, &code) in cells.iter().enumerate() {
+                let letter = alphabet.dense(folding.fold(code)).unwrap() as usize;
+                bits[letter * letter_len + row * words_per_row + col / 64] |= 1 << (col % 64);
+            }
+        }
+
+        Self {
+            shape,
+            directions,
+            folding,
+            alphabet,
+            words_per_row,
+            bits,
+        }
+    }
+
+    fn letter(&self, code: u8) -> Option<&[u64]> {
+        let len = self.words_per_row * self.shape.rows;
+        let letter = self.alphabet.dense(code)? as usize;
+
+        Some(&self.bits[letter * len..(letter + 1) * len])
+    }
+
+    /// Cells `needle` starts at when read along `axis`, or `None` when there are none.
+    fn starts(&self, needle: &[u8], axis: Direction) -> Option<Vec<u64>> {
+        let shape = self.shape;
+        let (row_step, col_step) = axis.offset();
+
+        // Wrapping lines never reach back into the cells they started from
+        if needle.len() > shape.line_len(axis) {
+            return None;
+        }
+
+        // Cells a suffix of the needle starts at, from its last letter back to the first one, each
+        // intersected with the cells before the starts of the previous suffix
+        let mut letters = needle.iter().rev().map(|&code| self.letter(code));
+        let mut starts = letters.next()??.to_vec();
+        let mut next = vec![];
+
+        for letter in letters {
+            let shifted = shift(&starts, col_step, shape);
+            and_next(letter?, &shifted, row_step, shape, &mut next);
+            std::mem::swap(&mut starts, &mut next);
+
+            if starts.iter().all(|&word| word == 0) {
+                return None;
+            }
+        }
+
+        Some(starts)
+    }
+
+    /// Starts of the occurrences of `word` along every axis, which has to be folded already.
+    fn searches<'w>(
+        &'w self,
+        word: &'w [u8],
+        reverse: &'w [u8],
+    ) -> impl Iterator<Item = (Direction, bool, Vec<u64>)> + 'w {
+        let directions = self.directions;
+        let palindrome = is_palindrome(word);
+
+        directions.axes_for(word.len()).flat_map(move |axis| {
+            let forward = directions.contains(axis);
+            // Segments reading the same both ways are only counted forward
+            let backward = directions.contains(axis.reverse()) && !(forward && palindrome);
+
+            [(word, false, forward), (reverse, true, backward)]
+                .into_iter()
+                .filter(|&(_, _, searched)| searched)
+                .filter_map(move |(needle, reversed, _)| {
+                    Some((axis, reversed, self.starts(needle, axis)?))
+                })
+        })
+    }
+}
+
+impl Solver for CrosswordBitsets {
+    fn count_occurrences(&self, word: &[u8]) -> usize {
+        if word.is_empty() {
+            return 0;
+        }
+
+        let word: &[u8] = &self.folding.fold_word(word);
+        let reverse = word.iter().rev().copied().collect::<SmallVec<[u8; 16]>>();
+
+        self.searches(word, &reverse)
+            .map(|(_, _, starts)| {
+                starts
+                    .iter()
+                    .map(|word| word.count_ones() as usize)
+                    .sum::<usize>()
+            })
+            .sum()
+    }
+
+    fn find_occurrences(&self, word: &[u8]) -> Vec<Match> {
+        if word.is_empty() {
+            return vec![];
+        }
+
+        let word: &[u8] = &self.folding.fold_word(word);
+        let reverse = word.iter().rev().copied().collect::<SmallVec<[u8; 16]>>();
+        let mut matches = vec![];
+
+        for (axis, reversed, starts) in self.searches(word, &reverse) {
+            for (idx, &bits) in starts.iter().enumerate() {
+                let mut bits = bits;
+
+                while bits != 0 {
+                    let row = idx / self.words_per_row;
+                    let col = idx % self.words_per_row * 64 + bits.trailing_zeros() as usize;
+                    bits &= bits - 1;
+
+                    matches.push(Match::from_segment(
+                        self.shape,
+                        (row, col),
+                        axis,
+                        word.len(),
+                        reversed,
+                    ));
+                }
+            }
+        }
+
+        matches
+    }
+}
+
+/// Fills `target` with `bits` intersected with `next`, both laid out row by row, where `next` has
+/// already been [`shift`]ed along the columns and every cell gets the bit of the cell `row_step`
+/// rows further.
+fn and_next(bits: &[u64], next: &[u64], row_step: isize, shape: Shape, target: &mut Vec<u64>) {
+    // Forward directions never go up, so the next cell is on the same row or the next one, which
+    // is a row of words further
+    let offset = row_step as usize * shape.cols.div_ceil(64);
+    let (inner, last) = bits.split_at(bits.len() - offset);
+
+    target.clear();
+    target.extend(
+        inner
+            .iter()
+            .zip(&next[offset..])
+            .map(|(&word, &next)| word & next),
+    );
+
+    // The last row continues on the first one
+    if shape.wrapping {
+        target.extend(last.iter().zip(next).map(|(&word, &next)| word & next));
+    } else {
+        target.resize(bits.len(), 0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn starts_along_axes() {
+        use Direction::*;
+
+        let crossword = Crossword::new(3, b"abcbcacab".to_vec().into_boxed_slice());
+        let wrapping = CrosswordBitsets::new(&crossword.clone().with_wrapping(true));
+        let bounded = CrosswordBitsets::new(&crossword);
+
+        assert_eq!(bounded.starts(b"bc", Right), Some(vec![0b010, 0b001, 0]));
+        assert_eq!(
+            wrapping.starts(b"bc", Right),
+            Some(vec![0b010, 0b001, 0b100])
+        );
+        assert_eq!(
+            bounded.starts(b"ccc", AntiDiagonal),
+            Some(vec![0b100, 0, 0])
+        );
+        assert_eq!(
+            wrapping.starts(b"ccc", AntiDiagonal),
+            Some(vec![0b100, 0b010, 0b001])
+        );
+        assert_eq!(wrapping.starts(b"cccc", AntiDiagonal), None);
+        assert_eq!(bounded.starts(b"acb", Diagonal), Some(vec![0b001, 0, 0]));
+        assert_eq!(
+            wrapping.starts(b"acb", Diagonal),
+            Some(vec![0b001, 0b100, 0b010])
+        );
+        assert_eq!(bounded.starts(b"abd", Right), None);
+    }
+}
@@ -0,0 +1,233 @@
+use fxhash::FxHashMap as HashMap;
+use smallvec::SmallVec;
+
+use crate::{
+    utils::is_palindrome, CellPath, Crossword, Direction, EstimateSize, Folding, PathSolver,
+};
+
+fn neighbours(crossword: &Crossword, cell: (usize, usize)) -> SmallVec<[(usize, usize); 8]> {
+    let shape = crossword.shape();
+
+    let mut neighbours = Direction::ALL
+        .into_iter()
+        .filter_map(|dir| shape.shift_point(cell, dir, 1))
+        .collect::<SmallVec<[_; 8]>>();
+
+    // Wrapping grids narrower than three cells reach the same neighbour in several directions
+    if shape.wrapping {
+        neighbours.sort_unstable();
+        neighbours.dedup();
+    }
+
+    neighbours
+}
+
+// A palindrome spelled along a path is spelled backwards along it as well, which is the same
+// occurrence, so only the path starting at the smaller end of the two is kept
+fn is_canonical(path: &[(usize, usize)], palindrome: bool) -> bool {
+    !palindrome || path.len() == 1 || path[0] < path[path.len() - 1]
+}
+
+/// Solver for Boggle-style words, spelled along chains of adjacent cells, diagonals included,
+/// without visiting a cell twice.
+pub struct BoggleSolver<'a> {
+    crossword: &'a Crossword,
+    folding: Folding,
+    // Cells holding each letter, so that paths only start where the word does
+    starts: HashMap<u8, Vec<(usize, usize)>>,
+}
+
+impl EstimateSize for BoggleSolver<'_> {
+    fn estimate_size(&self) -> usize {
+        size_of::<&Crossword>() + self.folding.estimate_size() + self.starts.estimate_size()
+    }
+}
+
+impl<'a> BoggleSolver<'a> {
+    pub fn new(crossword: &'a Crossword) -> Self {
+        let folding = crossword.folding();
+        let mut starts = HashMap::<u8, Vec<_>>::default();
+
+        for row in 0..crossword.rows() {
+            for col in 0..crossword.cols() {
+                starts
+                    .entry(folding.fold(crossword.get(row, col)))
+                    .or_default()
+                    .push((row, col));
+            }
+        }
+
+        Self {
+            crossword,
+            folding,
+            starts,
+        }
+    }
+
+    fn walk(
+        &self,
+        word: &[u8],
+        cell: (usize, usize),
+        visited: &mut [bool],
+        path: &mut CellPath,
+        found: &mut dyn FnMut(&CellPath),
+    ) {
+        let idx = cell.0 * self.crossword.cols() + cell.1;
+
+        path.push(cell);
+        visited[idx] = true;
+
+        if let Some(&next) = word.get(path.len()) {
+            for neighbour in neighbours(self.crossword, cell) {
+                let (row, col) = neighbour;
+
+                if !visited[row * self.crossword.cols() + col]
+                    && self.folding.fold(self.crossword.get(row, col)) == next
+                {
+                    self.walk(word, neighbour, visited, path, found);
+                }
+            }
+        } else {
+            found(path);
+        }
+
+        path.pop();
+        visited[idx] = false;
+    }
+
+    fn for_each_path(&self, word: &[u8], mut found: impl FnMut(&CellPath)) {
+        let word: &[u8] = &self.folding.fold_word(word);
+
+        let Some(first) = word.first() else {
+            return;
+        };
+
+        let palindrome = is_palindrome(word);
+        let mut visited = vec![false; self.crossword.rows() * self.crossword.cols()];
+        let mut path = CellPath::new();
+
+        let mut report = |path: &CellPath| {
+            if is_canonical(path, palindrome) {
+                found(path);
+            }
+        };
+
+        for &start in self.starts.get(first).into_iter().flatten() {
+            self.walk(word, start, &mut visited, &mut path, &mut report);
+        }
+    }
+}
+
+impl PathSolver for BoggleSolver<'_> {
+    fn count_paths(&self, word: &[u8]) -> usize {
+        let mut count = 0;
+        self.for_each_path(word, |_| count += 1);
+        count
+    }
+
+    fn find_paths(&self, word: &[u8]) -> Vec<CellPath> {
+        let mut paths = vec![];
+        self.for_each_path(word, |path| paths.push(path.clone()));
+        paths
+    }
+}
+
+/// Reference for [`BoggleSolver`], extending paths from every cell one neighbour at a time.
+pub struct NaiveBoggleSolver<'a> {
+    crossword: &'a Crossword,
+    folding: Folding,
+}
+
+impl EstimateSize for NaiveBoggleSolver<'_> {
+    fn estimate_size(&self) -> usize {
+        size_of::<&Crossword>() + self.folding.estimate_size()
+    }
+}
+
+impl<'a> NaiveBoggleSolver<'a> {
+    pub fn new(crossword: &'a Crossword) -> Self {
+        Self {
+            crossword,
+            folding: crossword.folding(),
+        }
+    }
+
+    fn extend(&self, word: &[u8], path: &mut CellPath, paths: &mut Vec<CellPath>) {
+        if path.len() == word.len() {
+            paths.push(path.clone());
+            return;
+        }
+
+        for next in neighbours(self.crossword, *path.last().unwrap()) {
+            if path.contains(&next)
+                || self.folding.fold(self.crossword.get(next.0, next.1)) != word[path.len()]
+            {
+                continue;
+            }
+
+            path.push(next);
+            self.extend(word, path, paths);
+            path.pop();
+        }
+    }
+}
+
+impl PathSolver for NaiveBoggleSolver<'_> {
+    fn count_paths(&self, word: &[u8]) -> usize {
+        self.find_paths(word).len()
+    }
+
+    fn find_paths(&self, word: &[u8]) -> Vec<CellPath> {
+        let word: &[u8] = &self.folding.fold_word(word);
+        let mut paths = vec![];
+
+        if word.is_empty() {
+            return paths;
+        }
+
+        for row in 0..self.crossword.rows() {
+            for col in 0..self.crossword.cols() {
+                if self.folding.fold(self.crossword.get(row, col)) == word[0] {
+                    self.extend(word, &mut CellPath::from_slice(&[(row, col)]), &mut paths);
+                }
+            }
+        }
+
+        let palindrome = is_palindrome(word);
+        paths.retain(|path| is_canonical(path, palindrome));
+
+        paths
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn adjacent_paths() {
+        let crossword = Crossword::new(3, b"catxaoyzt".to_vec().into_boxed_slice());
+        let solver = BoggleSolver::new(&crossword);
+
+        // c a t
+        // x a o
+        // y z t
+        let mut paths = solver.find_paths(b"cat");
+        paths.sort_unstable();
+
+        assert_eq!(
+            paths,
+            [
+                CellPath::from_slice(&[(0, 0), (0, 1), (0, 2)]),
+                CellPath::from_slice(&[(0, 0), (1, 1), (0, 2)]),
+                CellPath::from_slice(&[(0, 0), (1, 1), (2, 2)]),
+            ]
+        );
+
+        // Cells can't be reused, while the palindrome is only counted once per path
+        assert_eq!(solver.count_paths(b"tat"), 1);
+        assert_eq!(solver.count_paths(b"aa"), 1);
+        assert_eq!(solver.count_paths(b"cac"), 0);
+        assert_eq!(solver.count_paths(b"a"), 2);
+    }
+}
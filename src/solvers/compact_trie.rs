@@ -0,0 +1,206 @@
+use smallvec::SmallVec;
+
+use crate::{
+    Crossword, Direction, DirectionSet, EstimateSize, Match, Pattern, PatternSolver, Solver, Trie,
+    TrieNodes, TrieStorage,
+};
+
+// Cell and direction a line starts at, in narrower integers than the ones grids are addressed by
+type Origin = (u32, u32, Direction);
+
+/// Nodes of a trie laid out in preorder, so that the children of a node follow it one subtree after
+/// another and the whole subtree of a node is a contiguous range of ids.
+pub struct CompactNodes {
+    labels: Box<[u8]>,
+    counts: Box<[u32]>,
+    // Id past the last node of the subtree of each node
+    ends: Box<[u32]>,
+    // Sorted by node, so that the origins of a subtree are a contiguous range as well
+    origins: Box<[(u32, Origin)]>,
+}
+
+impl EstimateSize for CompactNodes {
+    fn estimate_size(&self) -> usize {
+        self.labels.estimate_size()
+            + self.counts.estimate_size()
+            + self.ends.estimate_size()
+            + self.origins.estimate_size()
+    }
+}
+
+#[derive(Default)]
+struct Builder {
+    labels: Vec<u8>,
+    counts: Vec<u32>,
+    ends: Vec<u32>,
+    origins: Vec<(u32, Origin)>,
+}
+
+impl Builder {
+    fn append(&mut self, nodes: &TrieNodes, node: u32, label: u8) -> u32 {
+        let id = self.labels.len() as u32;
+
+        self.labels.push(label);
+        self.counts.push(nodes.count(node) as u32);
+        self.ends.push(0);
+        self.origins.extend(
+            nodes
+                .origins(node)
+                .iter()
+                .map(|&(row, col, dir)| (id, (row as u32, col as u32, dir))),
+        );
+
+        let mut children = nodes.children(node).collect::<SmallVec<[_; 4]>>();
+        children.sort_unstable();
+
+        for (code, child) in children {
+            self.append(nodes, child, code);
+        }
+
+        self.ends[id as usize] = self.labels.len() as u32;
+        id
+    }
+}
+
+impl CompactNodes {
+    /// Lays out the subtrees of `roots` one after another, returning the ids the roots got.
+    pub fn new<const N: usize>(nodes: &TrieNodes, roots: [u32; N]) -> (Self, [u32; N]) {
+        let mut builder = Builder::default();
+        let roots = roots.map(|root| builder.append(nodes, root, 0));
+
+        let compact = Self {
+            labels: builder.labels.into_boxed_slice(),
+            counts: builder.counts.into_boxed_slice(),
+            ends: builder.ends.into_boxed_slice(),
+            origins: builder.origins.into_boxed_slice(),
+        };
+
+        (compact, roots)
+    }
+}
+
+impl TrieStorage for CompactNodes {
+    fn count(&self, node: u32) -> usize {
+        self.counts[node as usize] as usize
+    }
+
+    fn child(&self, node: u32, code: u8) -> Option<u32> {
+        // Children are sorted by their labels
+        self.children(node)
+            .find(|&(label, _)| label >= code)
+            .filter(|&(label, _)| label == code)
+            .map(|(_, child)| child)
+    }
+
+    fn children(&self, node: u32) -> impl Iterator<Item = (u8, u32)> + '_ {
+        let end = self.ends[node as usize];
+
+        let first = Some(node + 1).filter(|&child| child < end);
+
+        std::iter::successors(first, move |&child| {
+            Some(self.ends[child as usize]).filter(|&next| next < end)
+        })
+        .map(|child| (self.labels[child as usize], child))
+    }
+
+    fn collect_origins(&self, node: u32, target: &mut Vec<(usize, usize, Direction)>) {
+        let end = self.ends[node as usize];
+        let start = self.origins.partition_point(|&(id, _)| id < node);
+        let len = self.origins[start..].partition_point(|&(id, _)| id < end);
+
+        target.extend(
+            self.origins[start..start + len]
+                .iter()
+                .map(|&(_, (row, col, dir))| (row as usize, col as usize, dir)),
+        );
+    }
+}
+
+/// [`Trie`] with the same semantics, frozen into flat arrays once built, which take a fraction of the
+/// memory as nodes don't keep room for more children.
+pub struct CompactTrie(Trie<CompactNodes>);
+
+impl EstimateSize for CompactTrie {
+    fn estimate_size(&self) -> usize {
+        self.0.estimate_size()
+    }
+}
+
+impl CompactTrie {
+    pub fn new(crossword: &Crossword, word_len_limit: Option<usize>) -> Self {
+        Self::with_directions(crossword, word_len_limit, DirectionSet::ALL)
+    }
+
+    pub fn with_directions(
+        crossword: &Crossword,
+        word_len_limit: Option<usize>,
+        directions: DirectionSet,
+    ) -> Self {
+        let trie = Trie::with_directions(crossword, word_len_limit, directions);
+
+        Self(trie.map_nodes(|nodes, roots| CompactNodes::new(&nodes, roots)))
+    }
+}
+
+impl Solver for CompactTrie {
+    fn count_occurrences(&self, word: &[u8]) -> usize {
+        self.0.count_occurrences(word)
+    }
+
+    fn find_occurrences(&self, word: &[u8]) -> Vec<Match> {
+        self.0.find_occurrences(word)
+    }
+
+    fn count_many(&self, words: &[&[u8]]) -> Vec<usize> {
+        self.0.count_many(words)
+    }
+}
+
+impl PatternSolver for CompactTrie {
+    fn count_pattern(&self, pattern: &Pattern) -> usize {
+        self.0.count_pattern(pattern)
+    }
+
+    fn find_pattern(&self, pattern: &Pattern) -> Vec<Match> {
+        self.0.find_pattern(pattern)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn preorder_layout() {
+        use Direction::*;
+
+        let crossword = Crossword::new(2, b"abcabd".to_vec().into_boxed_slice());
+        let directions = DirectionSet::from_slice(&[Right, Left, Down]);
+        let trie = Trie::with_directions(&crossword, None, directions);
+
+        trie.map_nodes(|nodes, roots| {
+            let (compact, compact_roots) = CompactNodes::new(&nodes, roots);
+
+            for (root, compact_root) in roots.into_iter().zip(compact_roots) {
+                for word in [&b"ab"[..], b"abc", b"cba", b"abd", b"b", b"bb", b"ca", b"x"] {
+                    assert_eq!(
+                        nodes.count_occurrences(root, word),
+                        compact.count_occurrences(compact_root, word)
+                    );
+                }
+
+                let mut origins = vec![];
+                let mut compact_origins = vec![];
+
+                nodes.collect_origins(root, &mut origins);
+                compact.collect_origins(compact_root, &mut compact_origins);
+                origins.sort_unstable();
+                compact_origins.sort_unstable();
+
+                assert_eq!(origins, compact_origins);
+            }
+
+            (compact, compact_roots)
+        });
+    }
+}
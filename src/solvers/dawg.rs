@@ -0,0 +1,292 @@
+use smallvec::SmallVec;
+
+use crate::{Crossword, DirectionSet, EstimateSize, Match, Solver};
+
+use super::plans::{PlanLayout, Plans};
+
+// Missing suffix link of the initial state and missing end position of clones
+const NONE: u32 = u32::MAX;
+
+/// Suffix automaton of a single plan, i.e. its smallest DAWG, where every substring leads to a state
+/// shared by all substrings ending at the same positions. Lines repeating each other's endings share
+/// their states, unlike in a trie.
+struct Automaton {
+    // Transitions of every state sorted by symbol, laid out state by state
+    edge_starts: Box<[u32]>,
+    edge_symbols: Box<[u8]>,
+    edge_targets: Box<[u32]>,
+    // Number of end positions of every state
+    counts: Box<[u32]>,
+    // Plan offset past the prefix each state was created for, `NONE` for clones
+    ends: Box<[u32]>,
+    // Children of every state in the tree of suffix links, which end where their parent does
+    link_starts: Box<[u32]>,
+    link_children: Box<[u32]>,
+}
+
+impl EstimateSize for Automaton {
+    fn estimate_size(&self) -> usize {
+        self.edge_starts.estimate_size()
+            + self.edge_symbols.estimate_size()
+            + self.edge_targets.estimate_size()
+            + self.counts.estimate_size()
+            + self.ends.estimate_size()
+            + self.link_starts.estimate_size()
+            + self.link_children.estimate_size()
+    }
+}
+
+fn transition(edges: &[(u8, u32)], ch: u8) -> Option<u32> {
+    edges
+        .iter()
+        .find(|&&(symbol, _)| symbol == ch)
+        .map(|&(_, target)| target)
+}
+
+/// Prefix sums of `counts` as the starts of consecutive ranges, followed by the end of the last one.
+fn starts(counts: impl Iterator<Item = u32>) -> Box<[u32]> {
+    std::iter::once(0)
+        .chain(counts.scan(0, |total, count| {
+            *total += count;
+            Some(*total)
+        }))
+        .collect()
+}
+
+impl Automaton {
+    fn new(plan: &[u8]) -> Self {
+        let mut lens = vec![0u32];
+        let mut links = vec![NONE];
+        let mut edges = vec![SmallVec::<[(u8, u32); 2]>::new()];
+        let mut ends = vec![NONE];
+        let mut last = 0;
+
+        for (offset, &ch) in plan.iter().enumerate() {
+            let cur = lens.len() as u32;
+
+            lens.push(lens[last as usize] + 1);
+            links.push(0);
+            edges.push(SmallVec::new());
+            ends.push(offset as u32 + 1);
+
+            let mut state = last;
+
+            while state != NONE && transition(&edges[state as usize], ch).is_none() {
+                edges[state as usize].push((ch, cur));
+                state = links[state as usize];
+            }
+
+            if state != NONE {
+                let target = transition(&edges[state as usize], ch).unwrap();
+
+                if lens[state as usize] + 1 == lens[target as usize] {
+                    links[cur as usize] = target;
+                } else {
+                    let clone = lens.len() as u32;
+
+                    lens.push(lens[state as usize] + 1);
+                    links.push(links[target as usize]);
+                    edges.push(edges[target as usize].clone());
+                    ends.push(NONE);
+
+                    while state != NONE && transition(&edges[state as usize], ch) == Some(target) {
+                        let edge = edges[state as usize]
+                            .iter_mut()
+                            .find(|(symbol, _)| *symbol == ch)
+                            .unwrap();
+
+                        edge.1 = clone;
+                        state = links[state as usize];
+                    }
+
+                    links[target as usize] = clone;
+                    links[cur as usize] = clone;
+                }
+            }
+
+            last = cur;
+        }
+
+        // Every prefix adds its end position to the states along its suffix links, which are
+        // shorter, so longer states are added up first
+        let mut counts = ends
+            .iter()
+            .map(|&end| (end != NONE) as u32)
+            .collect::<Box<[u32]>>();
+        let mut order = (1..lens.len() as u32).collect::<Vec<_>>();
+        order.sort_unstable_by_key(|&state| std::cmp::Reverse(lens[state as usize]));
+
+        for state in order {
+            counts[links[state as usize] as usize] += counts[state as usize];
+        }
+
+        let mut children = (1..links.len() as u32)
+            .map(|state| (links[state as usize], state))
+            .collect::<Vec<_>>();
+        children.sort_unstable();
+
+        let mut child_counts = vec![0; links.len()];
+        children
+            .iter()
+            .for_each(|&(parent, _)| child_counts[parent as usize] += 1);
+
+        edges
+            .iter_mut()
+            .for_each(|edges| edges.sort_unstable_by_key(|&(symbol, _)| symbol));
+
+        Self {
+            edge_starts: starts(edges.iter().map(|edges| edges.len() as u32)),
+            edge_symbols: edges.iter().flatten().map(|&(symbol, _)| symbol).collect(),
+            edge_targets: edges.iter().flatten().map(|&(_, target)| target).collect(),
+            counts,
+            ends: ends.into_boxed_slice(),
+            link_starts: starts(child_counts.into_iter()),
+            link_children: children.into_iter().map(|(_, state)| state).collect(),
+        }
+    }
+
+    fn state(&self, word: &[u8]) -> Option<u32> {
+        word.iter().try_fold(0, |state, &ch| {
+            let edges = self.edge_starts[state as usize] as usize
+                ..self.edge_starts[state as usize + 1] as usize;
+            let idx = self.edge_symbols[edges.clone()].binary_search(&ch).ok()?;
+
+            Some(self.edge_targets[edges.start + idx])
+        })
+    }
+
+    fn count(&self, word: &[u8]) -> usize {
+        self.state(word)
+            .map(|state| self.counts[state as usize] as usize)
+            .unwrap_or_default()
+    }
+
+    /// Plan offsets past every occurrence of `word`.
+    fn ends(&self, word: &[u8]) -> Vec<usize> {
+        let mut ends = vec![];
+        let mut stack = self.state(word).into_iter().collect::<Vec<_>>();
+
+        while let Some(state) = stack.pop() {
+            let state = state as usize;
+
+            if self.ends[state] != NONE {
+                ends.push(self.ends[state] as usize);
+            }
+
+            stack.extend_from_slice(
+                &self.link_children
+                    [self.link_starts[state] as usize..self.link_starts[state + 1] as usize],
+            );
+        }
+
+        ends
+    }
+}
+
+/// Solver keeping a suffix automaton of every plan, which counts a word in time proportional to its
+/// length and takes less memory than a trie, as lines sharing their endings share states.
+pub struct CrosswordDawg {
+    layout: PlanLayout,
+    automata: [Automaton; 4],
+}
+
+impl EstimateSize for CrosswordDawg {
+    fn estimate_size(&self) -> usize {
+        self.layout.estimate_size() + self.automata.estimate_size()
+    }
+}
+
+impl CrosswordDawg {
+    pub fn new(crossword: &Crossword) -> Self {
+        Self::with_directions(crossword, DirectionSet::ALL)
+    }
+
+    pub fn with_directions(crossword: &Crossword, directions: DirectionSet) -> Self {
+        let (layout, plans) = Plans::new(crossword, directions).into_parts();
+
+        Self {
+            layout,
+            automata: plans.each_ref().map(|plan| Automaton::new(plan)),
+        }
+    }
+}
+
+impl Solver for CrosswordDawg {
+    fn count_occurrences(&self, word: &[u8]) -> usize {
+        if word.is_empty() {
+            return 0;
+        }
+
+        let word: &[u8] = &self.layout.folding().fold_word(word);
+        let reverse = word.iter().rev().copied().collect::<SmallVec<[u8; 16]>>();
+
+        self.layout
+            .searches(word)
+            .map(|(plan, reversed)| {
+                let automaton = &self.automata[plan];
+                let needle = if reversed { &reverse[..] } else { word };
+
+                // Repeated laps of wrapping lines have to be located to be told apart
+                if self.layout.is_wrapping() {
+                    automaton
+                        .ends(needle)
+                        .into_iter()
+                        .filter(|&end| self.layout.is_unique(plan, end - word.len(), word.len()))
+                        .count()
+                } else {
+                    automaton.count(needle)
+                }
+            })
+            .sum()
+    }
+
+    fn find_occurrences(&self, word: &[u8]) -> Vec<Match> {
+        if word.is_empty() {
+            return vec![];
+        }
+
+        let word: &[u8] = &self.layout.folding().fold_word(word);
+        let reverse = word.iter().rev().copied().collect::<SmallVec<[u8; 16]>>();
+
+        self.layout
+            .searches(word)
+            .flat_map(|(plan, reversed)| {
+                self.automata[plan]
+                    .ends(if reversed { &reverse } else { word })
+                    .into_iter()
+                    .map(move |end| end - word.len())
+                    .filter(move |&offset| self.layout.is_unique(plan, offset, word.len()))
+                    .map(move |offset| self.layout.to_match(plan, offset, word.len(), reversed))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn count_and_locate() {
+        let plan = b"banana.ananas.".repeat(10);
+        let automaton = Automaton::new(&plan);
+
+        for word in [&b"ana"[..], b"a", b"nas.b", b"banana", b"s"] {
+            let expected = plan
+                .windows(word.len())
+                .enumerate()
+                .filter(|(_, window)| window == &word)
+                .map(|(offset, _)| offset + word.len())
+                .collect::<Vec<_>>();
+
+            let mut ends = automaton.ends(word);
+            ends.sort_unstable();
+
+            assert_eq!(automaton.count(word), expected.len());
+            assert_eq!(ends, expected);
+        }
+
+        assert_eq!(automaton.count(b"nab"), 0);
+        assert_eq!(automaton.count(b"x"), 0);
+    }
+}
@@ -0,0 +1,333 @@
+use std::ops::Range;
+
+use smallvec::SmallVec;
+
+use crate::{Crossword, DirectionSet, EstimateSize, Match, Solver};
+
+use super::plans::{PlanLayout, Plans};
+
+// Sorts before every other byte, so the suffix of the terminator alone comes first
+const TERMINATOR: u8 = 0;
+// Positions between two stored rank checkpoints, which are scanned on every rank query
+const OCC_BLOCK: usize = 128;
+// Every n-th text position keeps its suffix array entry for locating matches
+const SA_SAMPLE: usize = 32;
+
+/// FM-index over a single plan, answering how many times a pattern occurs by backward search over
+/// the Burrows-Wheeler transform of the plan.
+struct FmIndex {
+    bwt: Box<[u8]>,
+    // Dense code of every byte present in the plan, `u8::MAX` for the missing ones
+    codes: Box<[u8; 256]>,
+    // Number of suffixes starting with a smaller symbol than each code
+    first: Box<[u32]>,
+    // Occurrences of each code before every block of the BWT, laid out block by block
+    occ: Box<[u32]>,
+    // Rows whose suffix starts at a sampled position, with a rank checkpoint for every word
+    sampled: Box<[u64]>,
+    sampled_ranks: Box<[u32]>,
+    samples: Box<[u32]>,
+}
+
+impl EstimateSize for FmIndex {
+    fn estimate_size(&self) -> usize {
+        self.bwt.estimate_size()
+            + size_of::<Box<[u8; 256]>>()
+            + self.codes.estimate_size()
+            + self.first.estimate_size()
+            + self.occ.estimate_size()
+            + self.sampled.estimate_size()
+            + self.sampled_ranks.estimate_size()
+            + self.samples.estimate_size()
+    }
+}
+
+/// Suffix array by prefix doubling, `text` has to end with a unique smallest byte.
+fn suffix_array(text: &[u8]) -> Vec<u32> {
+    let len = text.len();
+    let mut sa = (0..len as u32).collect::<Vec<_>>();
+    let mut rank = text.iter().map(|&ch| ch as u32).collect::<Vec<_>>();
+    let mut next_rank = vec![0; len];
+    let mut step = 1;
+
+    loop {
+        let key = |idx: u32| {
+            let idx = idx as usize;
+            (
+                rank[idx],
+                rank.get(idx + step)
+                    .map(|rank| rank + 1)
+                    .unwrap_or_default(),
+            )
+        };
+
+        sa.sort_unstable_by_key(|&idx| key(idx));
+
+        next_rank[sa[0] as usize] = 0;
+
+        for pair in sa.windows(2) {
+            next_rank[pair[1] as usize] =
+                next_rank[pair[0] as usize] + (key(pair[0]) < key(pair[1])) as u32;
+        }
+
+        std::mem::swap(&mut rank, &mut next_rank);
+
+        if rank[sa[len - 1] as usize] as usize == len - 1 {
+            return sa;
+        }
+
+        step *= 2;
+    }
+}
+
+impl FmIndex {
+    fn new(plan: &[u8]) -> Self {
+        let text = plan
+            .iter()
+            .copied()
+            .chain(std::iter::once(TERMINATOR))
+            .collect::<Vec<_>>();
+
+        let sa = suffix_array(&text);
+
+        let bwt = sa
+            .iter()
+            .map(|&idx| text[(idx as usize + text.len() - 1) % text.len()])
+            .collect::<Box<[u8]>>();
+
+        let mut byte_counts = [0u32; 256];
+
+        for &ch in &text {
+            byte_counts[ch as usize] += 1;
+        }
+
+        // Codes are assigned in byte order, so that they sort the same way as the bytes
+        let mut codes = Box::new([u8::MAX; 256]);
+        let mut counts = vec![];
+
+        for (ch, &count) in byte_counts.iter().enumerate().filter(|(_, &c)| c > 0) {
+            codes[ch] = counts.len() as u8;
+            counts.push(count);
+        }
+
+        let first = counts
+            .iter()
+            .scan(0, |total, &count| {
+                let start = *total;
+                *total += count;
+                Some(start)
+            })
+            .collect::<Box<[u32]>>();
+
+        let symbols = counts.len();
+        let mut occ = Vec::with_capacity((bwt.len() / OCC_BLOCK + 1) * symbols);
+        let mut running = vec![0; symbols];
+
+        for (idx, &ch) in bwt.iter().enumerate() {
+            if idx.is_multiple_of(OCC_BLOCK) {
+                occ.extend_from_slice(&running);
+            }
+
+            running[codes[ch as usize] as usize] += 1;
+        }
+
+        if bwt.len().is_multiple_of(OCC_BLOCK) {
+            occ.extend_from_slice(&running);
+        }
+
+        let mut sampled = vec![0u64; bwt.len().div_ceil(64)];
+        let mut samples = vec![];
+
+        for (row, &idx) in sa.iter().enumerate() {
+            if (idx as usize).is_multiple_of(SA_SAMPLE) {
+                sampled[row / 64] |= 1 << (row % 64);
+                samples.push(idx);
+            }
+        }
+
+        let sampled_ranks = sampled
+            .iter()
+            .scan(0, |total, word| {
+                let rank = *total;
+                *total += word.count_ones();
+                Some(rank)
+            })
+            .collect();
+
+        Self {
+            bwt,
+            codes,
+            first,
+            occ: occ.into_boxed_slice(),
+            sampled: sampled.into_boxed_slice(),
+            sampled_ranks,
+            samples: samples.into_boxed_slice(),
+        }
+    }
+
+    /// Occurrences of the symbol with `code` in the BWT before `row`.
+    fn rank(&self, code: u8, row: usize) -> usize {
+        let block = row / OCC_BLOCK;
+        let ch = self.bwt[block * OCC_BLOCK..row]
+            .iter()
+            .filter(|&&ch| self.codes[ch as usize] == code)
+            .count();
+
+        self.occ[block * self.first.len() + code as usize] as usize + ch
+    }
+
+    fn last_to_first(&self, row: usize) -> usize {
+        let code = self.codes[self.bwt[row] as usize];
+        self.first[code as usize] as usize + self.rank(code, row)
+    }
+
+    /// Rows of the suffixes starting with `pattern`.
+    fn search(&self, pattern: &[u8]) -> Range<usize> {
+        let mut rows = 0..self.bwt.len();
+
+        for &ch in pattern.iter().rev() {
+            let code = self.codes[ch as usize];
+
+            if code == u8::MAX {
+                return 0..0;
+            }
+
+            let first = self.first[code as usize] as usize;
+            rows = first + self.rank(code, rows.start)..first + self.rank(code, rows.end);
+
+            if rows.is_empty() {
+                return 0..0;
+            }
+        }
+
+        rows
+    }
+
+    /// Offset within the plan of the suffix at `row`.
+    fn locate(&self, mut row: usize) -> usize {
+        let mut steps = 0;
+
+        while self.sampled[row / 64] & (1 << (row % 64)) == 0 {
+            row = self.last_to_first(row);
+            steps += 1;
+        }
+
+        let below = self.sampled[row / 64] & ((1 << (row % 64)) - 1);
+        let sample = self.sampled_ranks[row / 64] as usize + below.count_ones() as usize;
+
+        self.samples[sample] as usize + steps
+    }
+}
+
+/// Solver keeping an FM-index of every plan instead of the plans themselves, so its memory stays
+/// within a small multiple of the grid while counting takes time proportional to the word length.
+pub struct CrosswordFmIndex {
+    layout: PlanLayout,
+    indexes: [FmIndex; 4],
+}
+
+impl EstimateSize for CrosswordFmIndex {
+    fn estimate_size(&self) -> usize {
+        self.layout.estimate_size() + self.indexes.estimate_size()
+    }
+}
+
+impl CrosswordFmIndex {
+    pub fn new(crossword: &Crossword) -> Self {
+        Self::with_directions(crossword, DirectionSet::ALL)
+    }
+
+    pub fn with_directions(crossword: &Crossword, directions: DirectionSet) -> Self {
+        let (layout, plans) = Plans::new(crossword, directions).into_parts();
+
+        Self {
+            layout,
+            indexes: plans.each_ref().map(|plan| FmIndex::new(plan)),
+        }
+    }
+}
+
+impl Solver for CrosswordFmIndex {
+    fn count_occurrences(&self, word: &[u8]) -> usize {
+        if word.is_empty() {
+            return 0;
+        }
+
+        let word: &[u8] = &self.layout.folding().fold_word(word);
+        let reverse = word.iter().rev().copied().collect::<SmallVec<[u8; 16]>>();
+
+        self.layout
+            .searches(word)
+            .map(|(plan, reversed)| {
+                let index = &self.indexes[plan];
+                let rows = index.search(if reversed { &reverse } else { word });
+
+                // Repeated laps of wrapping lines have to be located to be told apart
+                if self.layout.is_wrapping() {
+                    rows.filter(|&row| self.layout.is_unique(plan, index.locate(row), word.len()))
+                        .count()
+                } else {
+                    rows.len()
+                }
+            })
+            .sum()
+    }
+
+    fn find_occurrences(&self, word: &[u8]) -> Vec<Match> {
+        if word.is_empty() {
+            return vec![];
+        }
+
+        let word: &[u8] = &self.layout.folding().fold_word(word);
+        let reverse = word.iter().rev().copied().collect::<SmallVec<[u8; 16]>>();
+
+        self.layout
+            .searches(word)
+            .flat_map(|(plan, reversed)| {
+                let index = &self.indexes[plan];
+
+                index
+                    .search(if reversed { &reverse } else { word })
+                    .map(move |row| index.locate(row))
+                    .filter(move |&offset| self.layout.is_unique(plan, offset, word.len()))
+                    .map(move |offset| self.layout.to_match(plan, offset, word.len(), reversed))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn suffix_array_order() {
+        let text = b"banana.ananas.\0";
+        let sa = suffix_array(text);
+
+        for pair in sa.windows(2) {
+            assert!(text[pair[0] as usize..] < text[pair[1] as usize..]);
+        }
+    }
+
+    #[test]
+    fn search_and_locate() {
+        let plan = b"banana.ananas.".repeat(10);
+        let index = FmIndex::new(&plan);
+
+        let rows = index.search(b"ana");
+        let mut offsets = rows.map(|row| index.locate(row)).collect::<Vec<_>>();
+        offsets.sort_unstable();
+
+        let expected = plan
+            .windows(3)
+            .enumerate()
+            .filter(|(_, window)| window == b"ana")
+            .map(|(offset, _)| offset)
+            .collect::<Vec<_>>();
+
+        assert_eq!(offsets, expected);
+        assert!(index.search(b"nab").is_empty());
+        assert!(index.search(b"x").is_empty());
+    }
+}
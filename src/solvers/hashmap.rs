@@ -1,144 +1,374 @@
+use std::{
+    borrow::Cow,
+    io::{self, Read, Write},
+    sync::Arc,
+};
+
 use ahash::HashMap;
 use smallvec::SmallVec;
 
 use crate::{
-    utils::{canonical_order, is_palindrome},
-    Crossword, Direction, EstimateSize, Solver,
+    persist::{IndexHeader, Persist},
+    utils::{build_sharded, canonical_order, is_palindrome, lines_through},
+    CellChange, Crossword, CrosswordRef, Direction, DirectionSet, EstimateSize, Folding, Match,
+    Shape, Solver, UpdatableSolver,
 };
 
+use super::naive::scan_occurrences;
+
 type Positions = SmallVec<[(usize, usize, Direction); 2]>;
+type Counts = HashMap<SmallVec<[u8; STACK_WORD_LEN]>, usize>;
 const STACK_WORD_LEN: usize = 8;
 
 pub struct CrosswordHashMap<'a> {
     word_len: usize,
-    crossword: &'a Crossword,
-    complete_words: HashMap<SmallVec<[u8; STACK_WORD_LEN]>, usize>,
-    incomplete_words: HashMap<SmallVec<[u8; STACK_WORD_LEN]>, Positions>,
+    // Replaced by the edited grid the map is told about
+    crossword: CrosswordRef<'a>,
+    directions: DirectionSet,
+    folding: Folding,
+    words: Substrings,
+}
+
+/// Substrings of the lines of a grid, up to the word length of a [`CrosswordHashMap`].
+#[derive(Default)]
+struct Substrings {
+    // Substrings of lines readable both ways, keyed in canonical order
+    complete: Counts,
+    // Substrings of lines readable in a single direction, keyed as read
+    directed: Counts,
+    incomplete: HashMap<SmallVec<[u8; STACK_WORD_LEN]>, Positions>,
 }
 
 impl EstimateSize for CrosswordHashMap<'_> {
     fn estimate_size(&self) -> usize {
         self.word_len.estimate_size()
-            + std::mem::size_of::<&'_ Crossword>()
-            + self.complete_words.estimate_size()
-            + self.incomplete_words.estimate_size()
+            + self.crossword.estimate_size()
+            + self.directions.estimate_size()
+            + self.folding.estimate_size()
+            + self.words.complete.estimate_size()
+            + self.words.directed.estimate_size()
+            + self.words.incomplete.estimate_size()
     }
 }
 
-impl<'a> CrosswordHashMap<'a> {
-    pub fn new(crossword: &'a Crossword, word_len: usize) -> Self {
-        assert!(word_len > 0, "non-zero word length required");
+impl Substrings {
+    /// Counts the substrings of the line read from `(row, col)` along `dir` for up to `word_len`
+    /// cells, whose folded symbols `cell` gives, or takes them back when `removed` is set.
+    fn count_line(
+        &mut self,
+        shape: Shape,
+        cell: impl Fn((usize, usize)) -> u8,
+        directions: DirectionSet,
+        word_len: usize,
+        (row, col, dir): (usize, usize, Direction),
+        removed: bool,
+    ) {
+        let len = (1..=word_len)
+            .rev()
+            .find(|&len| shape.shift_point((row, col), dir, len - 1).is_some())
+            .unwrap();
+        let word = (0..len)
+            .map(|idx| cell(shape.shift_point_unchecked((row, col), dir, idx)))
+            .collect::<SmallVec<[u8; STACK_WORD_LEN]>>();
 
-        let mut complete_words: HashMap<SmallVec<[u8; STACK_WORD_LEN]>, usize> = HashMap::default();
-        let mut incomplete_words: HashMap<SmallVec<[u8; STACK_WORD_LEN]>, Positions> =
-            HashMap::default();
+        let mirrored = directions.is_mirrored(dir.axis());
+        let target = if mirrored {
+            &mut self.complete
+        } else {
+            &mut self.directed
+        };
+
+        for end in 1..=len {
+            // Single letters are only counted once per cell
+            if end == 1 && Some(dir) != directions.primary() {
+                continue;
+            }
 
-        fn add_all_substrings(
-            target: &mut HashMap<SmallVec<[u8; STACK_WORD_LEN]>, usize>,
-            word: impl Iterator<Item = u8>,
-        ) {
-            let mut current = SmallVec::<[u8; STACK_WORD_LEN]>::new();
+            let canonical = if mirrored {
+                canonical_order(&word[..end])
+            } else {
+                Cow::Borrowed(&word[..end])
+            };
 
-            for next in word {
-                current.push(next);
+            match target.get_mut(canonical.as_ref()) {
+                Some(counter) if removed => {
+                    *counter -= 1;
 
-                let canonical = canonical_order(&current);
+                    if *counter == 0 {
+                        target.remove(canonical.as_ref());
+                    }
+                }
+                Some(counter) => *counter += 1,
+                None => {
+                    debug_assert!(!removed, "only counted substrings are taken back");
 
-                if let Some(counter) = target.get_mut(canonical.as_ref()) {
-                    *counter += 1;
-                } else {
-                    target.insert(canonical.iter().copied().collect(), 1);
+                    if !removed {
+                        target.insert(canonical.iter().copied().collect(), 1);
+                    }
                 }
             }
         }
 
-        for row in 0..crossword.rows() {
-            for col in 0..crossword.cols() {
-                for dir in Direction::ALL {
-                    let Some(word) = crossword.get_word(row, col, dir, word_len) else {
-                        for len in (1..word_len).rev() {
-                            if let Some(found) = crossword.get_word(row, col, dir, len) {
-                                add_all_substrings(&mut complete_words, found);
-                                break;
-                            }
-                        }
-
-                        continue;
-                    };
-
-                    let word = word.collect::<SmallVec<[u8; STACK_WORD_LEN]>>();
-
-                    if word_len > 1 || dir == Direction::Right {
-                        add_all_substrings(
-                            &mut complete_words,
-                            word.iter().copied().take(word_len),
-                        );
-                    }
+        if len < word_len {
+            return;
+        }
+
+        if removed {
+            let positions = self.incomplete.get_mut(&word);
+            debug_assert!(positions.is_some(), "only counted lines are taken back");
+
+            if let Some(positions) = positions {
+                positions.retain(|&mut position| position != (row, col, dir));
 
-                    incomplete_words
-                        .entry(word)
-                        .or_default()
-                        .push((row, col, dir));
+                if positions.is_empty() {
+                    self.incomplete.remove(&word);
                 }
             }
+        } else {
+            self.incomplete
+                .entry(word)
+                .or_default()
+                .push((row, col, dir));
         }
+    }
+
+    fn merge(mut self, other: Self) -> Self {
+        for (target, other) in [
+            (&mut self.complete, other.complete),
+            (&mut self.directed, other.directed),
+        ] {
+            for (word, count) in other {
+                *target.entry(word).or_default() += count;
+            }
+        }
+
+        for (word, positions) in other.incomplete {
+            self.incomplete.entry(word).or_default().extend(positions);
+        }
+
+        self
+    }
+}
+
+impl<'a> CrosswordHashMap<'a> {
+    pub fn new(crossword: impl Into<CrosswordRef<'a>>, word_len: usize) -> Self {
+        Self::with_directions(crossword, word_len, DirectionSet::ALL)
+    }
+
+    pub fn with_directions(
+        crossword: impl Into<CrosswordRef<'a>>,
+        word_len: usize,
+        directions: DirectionSet,
+    ) -> Self {
+        let crossword = crossword.into();
+        assert!(word_len > 0, "non-zero word length required");
+
+        let folding = crossword.folding();
+
+        let words = build_sharded(
+            crossword.rows(),
+            Substrings::default,
+            |words, row| {
+                for col in 0..crossword.cols() {
+                    for dir in directions.axes().map(|axis| directions.orient(axis)) {
+                        words.count_line(
+                            crossword.shape(),
+                            |(row, col)| folding.fold(crossword.get(row, col)),
+                            directions,
+                            word_len,
+                            (row, col, dir),
+                            false,
+                        );
+                    }
+                }
+            },
+            Substrings::merge,
+        );
 
         Self {
             word_len,
             crossword,
-            complete_words,
-            incomplete_words,
+            directions,
+            folding,
+            words,
+        }
+    }
+
+    /// Writes the words as an index file, which [`CrosswordHashMap::load`] reads back for the same
+    /// grid.
+    pub fn save(&self, mut writer: impl Write) -> io::Result<()> {
+        let header = IndexHeader::new(
+            "hash",
+            Some(self.word_len),
+            self.directions,
+            &self.crossword,
+        );
+
+        header.write(&mut writer)?;
+        self.words.complete.persist(&mut writer)?;
+        self.words.directed.persist(&mut writer)?;
+        self.words.incomplete.persist(&mut writer)
+    }
+
+    /// Reads words saved by [`CrosswordHashMap::save`], failing when they were taken from another
+    /// grid than `crossword`.
+    pub fn load(
+        mut reader: impl Read,
+        crossword: impl Into<CrosswordRef<'a>>,
+    ) -> anyhow::Result<Self> {
+        let crossword = crossword.into();
+        let header = IndexHeader::read_for(&mut reader, "hash", &crossword)?;
+        let word_len = header.word_len.filter(|&len| len > 0);
+        let words = Substrings {
+            complete: Counts::restore(&mut reader)?,
+            directed: Counts::restore(&mut reader)?,
+            incomplete: HashMap::restore(&mut reader)?,
+        };
+
+        let shape = crossword.shape();
+        anyhow::ensure!(
+            words
+                .incomplete
+                .values()
+                .flatten()
+                .all(|&(row, col, _)| row < shape.rows && col < shape.cols),
+            "positions outside the grid"
+        );
+
+        Ok(Self {
+            word_len: word_len.ok_or_else(|| anyhow::anyhow!("missing word length"))?,
+            directions: header.directions,
+            folding: crossword.folding(),
+            crossword,
+            words,
+        })
+    }
+}
+
+impl UpdatableSolver for CrosswordHashMap<'_> {
+    fn update(&mut self, crossword: &Arc<Crossword>, changes: &[CellChange]) {
+        let shape = crossword.shape();
+        assert_eq!(
+            shape,
+            self.crossword.shape(),
+            "grids can't change their shape"
+        );
+
+        // Cells changed several times are read as they were before the first change
+        let mut old = HashMap::default();
+
+        for change in changes {
+            old.entry((change.row, change.col)).or_insert(change.old);
+        }
+
+        let starts = lines_through(shape, self.directions, changes, |_| self.word_len);
+        let dirs = self
+            .directions
+            .axes()
+            .map(|axis| self.directions.orient(axis))
+            .collect::<SmallVec<[_; 4]>>();
+
+        let folding = &self.folding;
+        let before = |(row, col)| {
+            folding.fold(
+                old.get(&(row, col))
+                    .copied()
+                    .unwrap_or(crossword.get(row, col)),
+            )
+        };
+        let after = |(row, col)| folding.fold(crossword.get(row, col));
+
+        // Lines are taken back as they were read before the changes
+        for (cell, removed) in [(&before as &dyn Fn(_) -> _, true), (&after, false)] {
+            for &(row, col) in &starts {
+                for &dir in &dirs {
+                    self.words.count_line(
+                        shape,
+                        cell,
+                        self.directions,
+                        self.word_len,
+                        (row, col, dir),
+                        removed,
+                    );
+                }
+            }
         }
+
+        self.crossword = CrosswordRef::Shared(crossword.clone());
+    }
+}
+
+impl CrosswordHashMap<'_> {
+    fn candidates(&self, word: &[u8], reversed: bool) -> impl Iterator<Item = Match> + '_ {
+        let needle = if reversed {
+            word.iter().rev().copied().collect()
+        } else {
+            SmallVec::<[u8; 16]>::from_slice(word)
+        };
+
+        let len = word.len();
+        let shape = self.crossword.shape();
+        let positions = self.words.incomplete.get(&needle[..self.word_len]);
+
+        positions
+            .into_iter()
+            .flatten()
+            .filter(move |&&(row, col, dir)| {
+                // Lines readable one way only are stored in the allowed direction
+                let reading = if reversed { dir.reverse() } else { dir };
+
+                self.directions.contains(reading)
+                    && self
+                        .crossword
+                        .get_word(row, col, dir, needle.len())
+                        .is_some_and(|found| {
+                            found
+                                .map(|code| self.folding.fold(code))
+                                .eq(needle.iter().copied())
+                        })
+            })
+            .map(move |&(row, col, dir)| Match::from_segment(shape, (row, col), dir, len, reversed))
+    }
+
+    fn long_occurrences<'w>(&'w self, word: &'w [u8]) -> impl Iterator<Item = Match> + 'w {
+        let reverse = (!is_palindrome(word)).then(|| self.candidates(word, true));
+
+        self.candidates(word, false)
+            .chain(reverse.into_iter().flatten())
     }
 }
 
 impl Solver for CrosswordHashMap<'_> {
     fn count_occurrences(&self, word: &[u8]) -> usize {
+        let word: &[u8] = &self.folding.fold_word(word);
+
         if word.len() <= self.word_len {
             return self
-                .complete_words
+                .words
+                .complete
                 .get(canonical_order(word).as_ref())
                 .copied()
-                .unwrap_or_default();
+                .unwrap_or_default()
+                + self.words.directed.get(word).copied().unwrap_or_default();
         }
 
-        let incomplete = word
-            .iter()
-            .copied()
-            .take(self.word_len)
-            .collect::<SmallVec<[u8; STACK_WORD_LEN]>>();
+        self.long_occurrences(word).count()
+    }
 
-        let mut count = 0;
+    fn find_occurrences(&self, word: &[u8]) -> Vec<Match> {
+        let word: &[u8] = &self.folding.fold_word(word);
 
-        if let Some(directions) = self.incomplete_words.get(&incomplete) {
-            for &(row, col, dir) in directions {
-                if let Some(found) = self.crossword.get_word(row, col, dir, word.len()) {
-                    if found.eq(word.iter().copied()) {
-                        count += 1;
-                    }
-                }
+        if word.len() <= self.word_len {
+            // Only counts are kept for short words, so their positions have to be looked up in
+            // the grid, but words that aren't present are skipped right away
+            if self.count_occurrences(word) == 0 {
+                return vec![];
             }
-        }
 
-        if !is_palindrome(word) {
-            let reverse = word
-                .iter()
-                .copied()
-                .rev()
-                .take(self.word_len)
-                .collect::<SmallVec<[u8; STACK_WORD_LEN]>>();
-
-            if let Some(directions) = self.incomplete_words.get(&reverse) {
-                for &(row, col, dir) in directions {
-                    if let Some(found) = self.crossword.get_word(row, col, dir, word.len()) {
-                        if found.eq(word.iter().rev().copied()) {
-                            count += 1;
-                        }
-                    }
-                }
-            }
+            return scan_occurrences(&self.crossword, self.directions, &self.folding, word)
+                .collect();
         }
 
-        count
+        self.long_occurrences(word).collect()
     }
 }
@@ -1,9 +1,28 @@
+mod ahocorasick;
+mod bitset;
+mod boggle;
+mod compact_trie;
+mod dawg;
+mod fm_index;
 mod hashmap;
 mod naive;
 mod needle;
+mod plans;
+mod registry;
+mod simd;
+mod streaming;
 mod trie;
 
+pub use ahocorasick::*;
+pub use bitset::*;
+pub use boggle::*;
+pub use compact_trie::*;
+pub use dawg::*;
+pub use fm_index::*;
 pub use hashmap::*;
 pub use naive::*;
 pub use needle::*;
+pub use registry::*;
+pub use simd::*;
+pub use streaming::*;
 pub use trie::*;
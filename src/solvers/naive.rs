@@ -1,44 +1,168 @@
-use crate::{utils::many_iter_eq, Crossword, Direction, EstimateSize, Solver};
+use smallvec::SmallVec;
 
-pub struct NaiveSolver<'a>(&'a Crossword);
+use crate::{
+    utils::many_iter_eq, Crossword, CrosswordRef, DirectionSet, EstimateSize, Folding, Match,
+    Pattern, PatternSolver, Solver,
+};
+
+pub struct NaiveSolver<'a> {
+    crossword: CrosswordRef<'a>,
+    directions: DirectionSet,
+    folding: Folding,
+}
 
 impl EstimateSize for NaiveSolver<'_> {
     fn estimate_size(&self) -> usize {
-        size_of::<Self>()
+        self.crossword.estimate_size()
+            + self.directions.estimate_size()
+            + self.folding.estimate_size()
     }
 }
 
 impl<'a> NaiveSolver<'a> {
-    pub fn new(crossword: &'a Crossword) -> Self {
-        Self(crossword)
+    pub fn new(crossword: impl Into<CrosswordRef<'a>>) -> Self {
+        Self::with_directions(crossword, DirectionSet::ALL)
     }
+
+    pub fn with_directions(
+        crossword: impl Into<CrosswordRef<'a>>,
+        directions: DirectionSet,
+    ) -> Self {
+        let crossword = crossword.into();
+
+        Self {
+            directions,
+            folding: crossword.folding(),
+            crossword,
+        }
+    }
+}
+
+/// Walks every line segment of length `len`, `matches` telling whether its letters read forward
+/// and backward are accepted.
+fn scan<'a>(
+    crossword: &'a Crossword,
+    directions: DirectionSet,
+    folding: &'a Folding,
+    len: usize,
+    matches: impl Fn(&mut dyn Iterator<Item = u8>) -> [bool; 2] + Copy + 'a,
+) -> impl Iterator<Item = Match> + 'a {
+    (0..crossword.rows())
+        .flat_map(move |row| (0..crossword.cols()).map(move |col| (row, col)))
+        .flat_map(move |(row, col)| {
+            directions.axes_for(len).filter_map(move |axis| {
+                let mut found = crossword
+                    .get_word(row, col, axis, len)?
+                    .map(|code| folding.fold(code));
+                let [forward, backward] = matches(&mut found);
+
+                let reversed = match (
+                    forward && directions.contains(axis),
+                    backward && directions.contains(axis.reverse()),
+                ) {
+                    (true, _) => false,
+                    (false, true) => true,
+                    _ => return None,
+                };
+
+                Some(Match::from_segment(
+                    crossword.shape(),
+                    (row, col),
+                    axis,
+                    len,
+                    reversed,
+                ))
+            })
+        })
+}
+
+/// Occurrences of `word`, which has to be folded already.
+pub(crate) fn scan_occurrences<'a>(
+    crossword: &'a Crossword,
+    directions: DirectionSet,
+    folding: &'a Folding,
+    word: &'a [u8],
+) -> impl Iterator<Item = Match> + 'a {
+    scan(crossword, directions, folding, word.len(), move |found| {
+        many_iter_eq(
+            found,
+            [&mut word.iter().copied(), &mut word.iter().rev().copied()],
+        )
+    })
+}
+
+fn scan_pattern<'a>(
+    crossword: &'a Crossword,
+    directions: DirectionSet,
+    folding: &'a Folding,
+    pattern: &'a Pattern,
+) -> impl Iterator<Item = Match> + 'a {
+    scan(
+        crossword,
+        directions,
+        folding,
+        pattern.len(),
+        move |found| {
+            let found = found.collect::<SmallVec<[u8; 16]>>();
+
+            [
+                pattern.matches(found.iter().copied()),
+                pattern.matches(found.iter().rev().copied()),
+            ]
+        },
+    )
 }
 
 impl Solver for NaiveSolver<'_> {
     fn count_occurrences(&self, word: &[u8]) -> usize {
-        let crossword = &self.0;
-        let mut occurrences = 0;
-
-        for row in 0..crossword.rows() {
-            for col in 0..crossword.cols() {
-                for dir in Direction::ALL {
-                    let Some(found) = crossword.get_word(row, col, dir, word.len()) else {
-                        continue;
-                    };
-
-                    if many_iter_eq(
-                        found,
-                        [&mut word.iter().copied(), &mut word.iter().rev().copied()],
-                    )
-                    .into_iter()
-                    .any(|v| v)
-                    {
-                        occurrences += 1;
-                    }
-                }
-            }
+        if word.is_empty() {
+            return 0;
         }
 
-        occurrences
+        let word = self.folding.fold_word(word);
+        scan_occurrences(&self.crossword, self.directions, &self.folding, &word).count()
+    }
+
+    fn find_occurrences(&self, word: &[u8]) -> Vec<Match> {
+        if word.is_empty() {
+            return vec![];
+        }
+
+        let word = self.folding.fold_word(word);
+        scan_occurrences(&self.crossword, self.directions, &self.folding, &word).collect()
+    }
+}
+
+impl NaiveSolver<'_> {
+    /// Patterns of a fixed length to scan for `pattern`, up to the longest line of the grid.
+    fn expand(&self, pattern: &Pattern) -> Vec<Pattern> {
+        let max_len = self
+            .directions
+            .axes()
+            .map(|axis| self.crossword.shape().line_len(axis))
+            .max()
+            .unwrap_or_default();
+
+        self.folding.fold_pattern(pattern).expand(max_len).collect()
+    }
+}
+
+impl PatternSolver for NaiveSolver<'_> {
+    fn count_pattern(&self, pattern: &Pattern) -> usize {
+        self.expand(pattern)
+            .iter()
+            .map(|pattern| {
+                scan_pattern(&self.crossword, self.directions, &self.folding, pattern).count()
+            })
+            .sum()
+    }
+
+    fn find_pattern(&self, pattern: &Pattern) -> Vec<Match> {
+        self.expand(pattern)
+            .iter()
+            .flat_map(|pattern| {
+                scan_pattern(&self.crossword, self.directions, &self.folding, pattern)
+            })
+            .collect()
     }
 }
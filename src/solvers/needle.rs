@@ -1,100 +1,261 @@
-use std::iter::once;
+use std::{
+    fs::File,
+    io::{self, Write},
+    path::Path,
+};
 
+use ahash::HashMap;
 use memchr::memmem::Finder;
-use smallvec::SmallVec;
+use memmap2::Mmap;
+use regex::bytes::Regex;
+use smallvec::{smallvec, SmallVec};
 
-use crate::{utils::is_palindrome, Crossword, EstimateSize, Solver};
+use crate::{persist::IndexHeader, Crossword, DirectionSet, EstimateSize, Match, Solver};
 
-const DELIM: u8 = b'.';
+use super::plans::Plans;
+
+// Needles grouped by length, pointing to indices of the words they count
+type Patterns<'w> = HashMap<usize, HashMap<&'w [u8], SmallVec<[usize; 1]>>>;
 
 pub struct CrosswordNeedleSearch {
-    plans: [Box<[u8]>; 4],
+    plans: Plans,
 }
 
 impl EstimateSize for CrosswordNeedleSearch {
     fn estimate_size(&self) -> usize {
         self.plans.estimate_size()
     }
+
+    fn estimate_mapped_size(&self) -> usize {
+        self.plans.estimate_mapped_size()
+    }
 }
 
 impl CrosswordNeedleSearch {
     pub fn new(crossword: &Crossword) -> Self {
-        let rows = crossword.rows();
-        let cols = crossword.cols();
-
-        let direct = crossword
-            .get_rows()
-            .flat_map(|v| v.iter().copied().chain(once(DELIM)))
-            .collect::<Box<[u8]>>();
-
-        let transposed = crossword
-            .get_cols()
-            .flat_map(|v| v.chain(once(DELIM)))
-            .collect::<Box<[u8]>>();
-
-        let diagonal = (0..rows)
-            .flat_map(|diag_idx| {
-                let start_row = rows - diag_idx - 1;
-                let data = &crossword.data;
-
-                (0..(rows - start_row).min(cols))
-                    .map(move |j| data[(start_row + j) * cols + j])
-                    .chain(once(DELIM))
-            })
-            .chain((1..cols).flat_map(|start_col| {
-                let data = &crossword.data;
-
-                (0..(cols - start_col).min(rows))
-                    .map(move |j| data[j * cols + start_col + j])
-                    .chain(once(DELIM))
-            }))
-            .collect::<Box<[u8]>>();
-
-        let anti_diagonal = (0..cols)
-            .flat_map(|start_col| {
-                let data = &crossword.data;
-
-                (0..(1 + start_col).min(rows))
-                    .map(move |j| data[j * cols + start_col - j])
-                    .chain(once(DELIM))
-            })
-            .chain((1..rows).flat_map(|start_row| {
-                let data = &crossword.data;
-
-                (0..(rows - start_row).min(cols))
-                    .map(move |j| data[(start_row + j) * cols + cols - j - 1])
-                    .chain(once(DELIM))
-            }))
-            .collect::<Box<[u8]>>();
+        Self::with_directions(crossword, DirectionSet::ALL)
+    }
 
+    pub fn with_directions(crossword: &Crossword, directions: DirectionSet) -> Self {
         Self {
-            plans: [direct, transposed, diagonal, anti_diagonal],
+            plans: Plans::new(crossword, directions),
+        }
+    }
+
+    /// Writes the plans as an index file, which [`CrosswordNeedleSearch::map`] maps back for the
+    /// same grid.
+    pub fn save(&self, mut writer: impl Write) -> io::Result<()> {
+        self.header().write(&mut writer)?;
+        self.plans.save(&mut writer)
+    }
+
+    fn header(&self) -> IndexHeader {
+        let layout = &self.plans.layout;
+
+        IndexHeader {
+            solver: "needle".into(),
+            word_len: None,
+            directions: layout.directions(),
+            grid_hash: layout.grid_hash(),
+        }
+    }
+
+    /// Sizes the search would take in memory and in the mapped file once saved and mapped back by
+    /// [`CrosswordNeedleSearch::map`], without writing the file.
+    pub fn estimate_mapped(&self) -> (usize, usize) {
+        let mut header = vec![];
+        self.header()
+            .write(&mut header)
+            .expect("writing to memory doesn't fail");
+
+        self.plans.estimate_mapped(header.len())
+    }
+
+    /// Maps an index file saved by [`CrosswordNeedleSearch::save`], failing when it was built for
+    /// another grid than `crossword`. Plans are searched right in the file, so they're only paged
+    /// in as they're read, and the file mustn't change while it's mapped.
+    pub fn map(path: impl AsRef<Path>, crossword: &Crossword) -> anyhow::Result<Self> {
+        let file = File::open(path)?;
+        // SAFETY: index files aren't modified while they're in use, as documented above
+        let map = unsafe { Mmap::map(&file)? };
+
+        let mut reader = &map[..];
+        let header = IndexHeader::read_for(&mut reader, "needle", crossword)?;
+        let offset = map.len() - reader.len();
+
+        Ok(Self {
+            plans: Plans::map(crossword, header.directions, map, offset)?,
+        })
+    }
+}
+
+impl CrosswordNeedleSearch {
+    /// Matches of `regex` along every allowed line, read both forward and reversed. Matches are
+    /// confined to a single line and don't overlap within one reading of it, while segments matched
+    /// both ways are only reported once, like palindromes.
+    pub fn find_regex(&self, regex: &Regex) -> Vec<Match> {
+        let layout = &self.plans.layout;
+        let directions = layout.directions();
+
+        let mut matches = vec![];
+        let mut found = vec![];
+        let mut reverse = vec![];
+
+        for axis in directions.axes() {
+            let plan = axis as usize;
+
+            for (start, line) in self.plans.lines(plan) {
+                found.clear();
+
+                if directions.contains(axis) {
+                    found.extend(regex.find_iter(line).map(|m| (m.start(), m.len(), false)));
+                }
+
+                if directions.contains(axis.reverse()) {
+                    let forward = found.len();
+
+                    reverse.clear();
+                    reverse.extend(line.iter().rev());
+
+                    for m in regex.find_iter(&reverse) {
+                        let segment = (line.len() - m.end(), m.len(), false);
+
+                        if found[..forward].binary_search(&segment).is_err() {
+                            found.push((segment.0, segment.1, true));
+                        }
+                    }
+                }
+
+                for &(offset, len, reversed) in &found {
+                    match len {
+                        0 => continue,
+                        _ if directions.axes_for(len).all(|read| read != axis) => continue,
+                        _ if !layout.is_unique(plan, start + offset, len) => continue,
+                        _ => {}
+                    }
+
+                    let mut m = layout.to_match(plan, start + offset, len, reversed);
+
+                    if len == 1 {
+                        m.dir = directions.primary().unwrap();
+                    }
+
+                    matches.push(m);
+                }
+            }
         }
+
+        matches
+    }
+
+    pub fn count_regex(&self, regex: &Regex) -> usize {
+        self.find_regex(regex).len()
     }
 }
 
 impl Solver for CrosswordNeedleSearch {
     fn count_occurrences(&self, word: &[u8]) -> usize {
+        if word.is_empty() {
+            return 0;
+        }
+
+        let word: &[u8] = &self.plans.layout.folding().fold_word(word);
         let reverse = word.iter().rev().copied().collect::<SmallVec<[u8; 16]>>();
-        let needles = {
-            let mut needles = SmallVec::<[Finder; 2]>::new();
-            needles.push(Finder::new(word));
+        let needles = [Finder::new(word), Finder::new(&reverse)];
 
-            if !is_palindrome(word) {
-                needles.push(Finder::new(&reverse));
-            }
+        self.plans
+            .layout
+            .searches(word)
+            .map(|(plan, reversed)| {
+                needles[reversed as usize]
+                    .find_iter(&self.plans[plan])
+                    .filter(|&offset| self.plans.layout.is_unique(plan, offset, word.len()))
+                    .count()
+            })
+            .sum::<usize>()
+    }
 
-            needles
-        };
+    fn find_occurrences(&self, word: &[u8]) -> Vec<Match> {
+        if word.is_empty() {
+            return vec![];
+        }
+
+        let word: &[u8] = &self.plans.layout.folding().fold_word(word);
+        let reverse = word.iter().rev().copied().collect::<SmallVec<[u8; 16]>>();
+        let needles = [Finder::new(word), Finder::new(&reverse)];
 
         self.plans
-            .iter()
-            .flat_map(|plan| {
-                needles
-                    .iter()
-                    .map(move |needle| needle.find_iter(plan).count())
+            .layout
+            .searches(word)
+            .flat_map(|(plan, reversed)| {
+                needles[reversed as usize]
+                    .find_iter(&self.plans[plan])
+                    .filter(move |&offset| self.plans.layout.is_unique(plan, offset, word.len()))
+                    .map(move |offset| {
+                        self.plans
+                            .layout
+                            .to_match(plan, offset, word.len(), reversed)
+                    })
             })
-            .sum::<usize>()
+            .collect()
+    }
+
+    /// Looks the windows of every plan up among the needles of the same length, so that a plan is
+    /// read once per distinct length of the words rather than once per word.
+    fn count_many(&self, words: &[&[u8]]) -> Vec<usize> {
+        let folded = words
+            .iter()
+            .map(|word| self.plans.layout.folding().fold_word(word))
+            .collect::<Vec<_>>();
+        let words = folded.iter().map(|word| word.as_ref()).collect::<Vec<_>>();
+
+        let reversed = words
+            .iter()
+            .map(|word| word.iter().rev().copied().collect::<SmallVec<[u8; 16]>>())
+            .collect::<Vec<_>>();
+
+        let mut patterns: [Patterns; 4] = Default::default();
+
+        for (idx, word) in words
+            .iter()
+            .enumerate()
+            .filter(|(_, word)| !word.is_empty())
+        {
+            for (plan, reversed_needle) in self.plans.layout.searches(word) {
+                let needle = if reversed_needle {
+                    &reversed[idx][..]
+                } else {
+                    word
+                };
+
+                patterns[plan]
+                    .entry(needle.len())
+                    .or_default()
+                    .entry(needle)
+                    .and_modify(|indices| indices.push(idx))
+                    .or_insert_with(|| smallvec![idx]);
+            }
+        }
+
+        let mut counts = vec![0; words.len()];
+
+        for ((plan_idx, plan), by_len) in self.plans.iter().enumerate().zip(&patterns) {
+            for (&len, needles) in by_len {
+                for (offset, window) in plan.windows(len).enumerate() {
+                    let Some(indices) = needles.get(window) else {
+                        continue;
+                    };
+
+                    if self.plans.layout.is_unique(plan_idx, offset, len) {
+                        for &idx in indices {
+                            counts[idx] += 1;
+                        }
+                    }
+                }
+            }
+        }
+
+        counts
     }
 }
 
@@ -112,4 +273,54 @@ mod tests {
         assert_eq!(needle.plans[2].as_ref(), b"g.dh.aei.bf.c.");
         assert_eq!(needle.plans[3].as_ref(), b"a.bd.ceg.fh.i.");
     }
+
+    #[test]
+    fn regex_lines() {
+        use crate::Direction::*;
+
+        let crossword = Crossword::new(3, b"abcbcaxyz".to_vec().into_boxed_slice());
+        let needle = CrosswordNeedleSearch::new(&crossword);
+
+        // Lines are never joined, so "c.b" in "abc.bca" isn't a match
+        let mut found = needle.find_regex(&Regex::new("c.?b").unwrap());
+        found.sort_unstable();
+
+        assert_eq!(
+            found,
+            [
+                Match {
+                    row: 0,
+                    col: 2,
+                    dir: Left,
+                    len: 2
+                },
+                Match {
+                    row: 1,
+                    col: 1,
+                    dir: Left,
+                    len: 2
+                },
+                Match {
+                    row: 1,
+                    col: 1,
+                    dir: Up,
+                    len: 2
+                },
+            ]
+        );
+
+        assert_eq!(needle.count_regex(&Regex::new("bb").unwrap()), 1);
+        assert_eq!(needle.count_regex(&Regex::new("[a-c]").unwrap()), 6);
+        assert_eq!(needle.count_regex(&Regex::new("q*").unwrap()), 0);
+    }
+
+    #[test]
+    fn empty_words() {
+        let crossword = Crossword::new(3, b"abcdefghi".to_vec().into_boxed_slice());
+        let needle = CrosswordNeedleSearch::new(&crossword);
+
+        assert_eq!(needle.count_occurrences(b""), 0);
+        assert_eq!(needle.find_occurrences(b""), []);
+        assert_eq!(needle.count_many(&[b"", b"ab", b""]), [0, 1, 0]);
+    }
 }
@@ -0,0 +1,451 @@
+use std::{
+    io::{self, Write},
+    iter::once,
+    ops::{Index, Range},
+};
+
+use memchr::memchr_iter;
+use memmap2::Mmap;
+
+use crate::{
+    persist::Persist, utils::is_palindrome, Crossword, Direction, DirectionSet, EstimateSize,
+    Folding, Match, Shape,
+};
+
+pub(crate) const DELIM: u8 = b'.';
+
+/// Lines of a grid along each forward direction, laid out one after another and separated by
+/// [`DELIM`], so that a whole axis can be searched as a single buffer.
+pub(crate) struct Plans {
+    pub layout: PlanLayout,
+    plans: PlanBytes,
+}
+
+// Plans are either built in memory or mapped from an index file, laid out there as they're searched
+enum PlanBytes {
+    Owned([Box<[u8]>; 4]),
+    Mapped {
+        map: Mmap,
+        ranges: [Range<usize>; 4],
+    },
+}
+
+/// Where the lines of [`Plans`] start, which is enough to map offsets back to the grid without
+/// keeping the plans themselves.
+pub(crate) struct PlanLayout {
+    shape: Shape,
+    directions: DirectionSet,
+    folding: Folding,
+    grid_hash: u64,
+    line_starts: [Box<[usize]>; 4],
+}
+
+impl EstimateSize for Plans {
+    fn estimate_size(&self) -> usize {
+        self.layout.estimate_size()
+            + match &self.plans {
+                PlanBytes::Owned(plans) => plans.estimate_size(),
+                PlanBytes::Mapped { .. } => size_of::<PlanBytes>(),
+            }
+    }
+
+    fn estimate_mapped_size(&self) -> usize {
+        match &self.plans {
+            PlanBytes::Owned(_) => 0,
+            PlanBytes::Mapped { map, .. } => map.len(),
+        }
+    }
+}
+
+impl EstimateSize for PlanLayout {
+    fn estimate_size(&self) -> usize {
+        self.shape.estimate_size()
+            + self.directions.estimate_size()
+            + self.folding.estimate_size()
+            + self.grid_hash.estimate_size()
+            + self.line_starts.estimate_size()
+    }
+}
+
+impl Index<usize> for Plans {
+    type Output = [u8];
+
+    fn index(&self, plan: usize) -> &Self::Output {
+        match &self.plans {
+            PlanBytes::Owned(plans) => &plans[plan],
+            PlanBytes::Mapped { map, ranges } => &map[ranges[plan].clone()],
+        }
+    }
+}
+
+// Lines of a wrapping grid are cycles, each laid out once followed by all but its last cell again,
+// so that every word fitting the cycle can be read from a start within its first lap
+fn wrapping_plan(crossword: &Crossword, axis: Direction) -> Box<[u8]> {
+    let shape = crossword.shape();
+    let period = shape.period(axis);
+
+    (0..shape.lines(axis))
+        .flat_map(|line| {
+            let origin = if axis == Direction::Right {
+                (line, 0)
+            } else {
+                (0, line)
+            };
+
+            (0..2 * period - 1)
+                .map(move |i| {
+                    let (row, col) = shape.shift_point_unchecked(origin, axis, i);
+                    crossword.get(row, col)
+                })
+                .chain(once(DELIM))
+        })
+        .collect()
+}
+
+impl Plans {
+    pub fn new(crossword: &Crossword, directions: DirectionSet) -> Self {
+        let shape = crossword.shape();
+
+        let mut plans = if shape.wrapping {
+            Direction::FORWARD.map(|axis| wrapping_plan(crossword, axis))
+        } else {
+            Self::bounded_plans(crossword)
+        };
+
+        let folding = crossword.folding();
+
+        // Lines that can't be read in any allowed direction are left out
+        for (plan, axis) in plans.iter_mut().zip(Direction::FORWARD) {
+            if !directions.has_axis(axis) {
+                *plan = Box::default();
+            }
+
+            // Delimiters are kept as they are, as no symbol is stored as them, see `Charset`
+            plan.iter_mut().for_each(|code| *code = folding.fold(*code));
+        }
+
+        let line_starts = plans.each_ref().map(|plan| {
+            once(0)
+                .chain(memchr_iter(DELIM, plan).map(|idx| idx + 1))
+                .filter(|&start| start < plan.len())
+                .collect::<Box<[usize]>>()
+        });
+
+        Self {
+            layout: PlanLayout {
+                shape,
+                directions,
+                folding,
+                grid_hash: crossword.grid_hash(),
+                line_starts,
+            },
+            plans: PlanBytes::Owned(plans),
+        }
+    }
+
+    /// Writes the plans after where their lines start, so that [`Plans::map`] can search them
+    /// right in the file.
+    pub fn save(&self, writer: &mut dyn Write) -> io::Result<()> {
+        self.save_layout(writer)?;
+        self.iter().try_for_each(|plan| writer.write_all(plan))
+    }
+
+    // Everything written before the plans themselves
+    fn save_layout(&self, writer: &mut dyn Write) -> io::Result<()> {
+        self.layout.line_starts.persist(writer)?;
+        std::array::from_fn::<_, 4, _>(|plan| self[plan].len()).persist(writer)
+    }
+
+    /// Sizes the plans would take in memory and in the mapped file once saved `offset` bytes into
+    /// a file and mapped back, without writing it.
+    pub fn estimate_mapped(&self, offset: usize) -> (usize, usize) {
+        let mut layout = vec![];
+        self.save_layout(&mut layout)
+            .expect("writing to memory doesn't fail");
+
+        let plans = self.iter().map(<[u8]>::len).sum::<usize>();
+
+        (
+            self.layout.estimate_size() + size_of::<PlanBytes>(),
+            offset + layout.len() + plans,
+        )
+    }
+
+    /// Plans saved by [`Plans::save`] from `offset` of `map` on, which are searched where they're
+    /// mapped, while only their line starts are read into memory.
+    pub fn map(
+        crossword: &Crossword,
+        directions: DirectionSet,
+        map: Mmap,
+        offset: usize,
+    ) -> io::Result<Self> {
+        let mut reader = map.get(offset..).unwrap_or_default();
+        let line_starts = <[Box<[usize]>; 4]>::restore(&mut reader)?;
+        let lens = <[usize; 4]>::restore(&mut reader)?;
+
+        let mut end = map.len() - reader.len();
+        let ranges = lens.map(|len| {
+            let start = end;
+            end = end.saturating_add(len);
+            start..end
+        });
+
+        let layout = PlanLayout {
+            shape: crossword.shape(),
+            directions,
+            folding: crossword.folding(),
+            grid_hash: crossword.grid_hash(),
+            line_starts,
+        };
+
+        // Lines have to be laid out as they're built for the grid, or offsets would be mapped
+        // outside of it
+        let valid = end == map.len()
+            && (0..4).all(|plan| layout.is_laid_out(plan, &map[ranges[plan].clone()]));
+
+        if !valid {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "plans don't match the lines of the grid",
+            ));
+        }
+
+        Ok(Self {
+            layout,
+            plans: PlanBytes::Mapped { map, ranges },
+        })
+    }
+
+    fn bounded_plans(crossword: &Crossword) -> [Box<[u8]>; 4] {
+        let rows = crossword.rows();
+        let cols = crossword.cols();
+
+        let direct = crossword
+            .get_rows()
+            .flat_map(|v| v.iter().copied().chain(once(DELIM)))
+            .collect::<Box<[u8]>>();
+
+        let transposed = crossword
+            .get_cols()
+            .flat_map(|v| v.chain(once(DELIM)))
+            .collect::<Box<[u8]>>();
+
+        let diagonal = (0..rows)
+            .flat_map(|diag_idx| {
+                let start_row = rows - diag_idx - 1;
+                let data = &crossword.data;
+
+                (0..(rows - start_row).min(cols))
+                    .map(move |j| data[(start_row + j) * cols + j])
+                    .chain(once(DELIM))
+            })
+            .chain((1..cols).flat_map(|start_col| {
+                let data = &crossword.data;
+
+                (0..(cols - start_col).min(rows))
+                    .map(move |j| data[j * cols + start_col + j])
+                    .chain(once(DELIM))
+            }))
+            .collect::<Box<[u8]>>();
+
+        let anti_diagonal = (0..cols)
+            .flat_map(|start_col| {
+                let data = &crossword.data;
+
+                (0..(1 + start_col).min(rows))
+                    .map(move |j| data[j * cols + start_col - j])
+                    .chain(once(DELIM))
+            })
+            .chain((1..rows).flat_map(|start_row| {
+                let data = &crossword.data;
+
+                (0..(rows - start_row).min(cols))
+                    .map(move |j| data[(start_row + j) * cols + cols - j - 1])
+                    .chain(once(DELIM))
+            }))
+            .collect::<Box<[u8]>>();
+
+        [direct, transposed, diagonal, anti_diagonal]
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &[u8]> + '_ {
+        (0..4).map(|plan| &self[plan])
+    }
+
+    /// Lines of `plan` without their delimiters, paired with the offsets they start at.
+    pub fn lines(&self, plan: usize) -> impl Iterator<Item = (usize, &[u8])> + '_ {
+        let plan = &self[plan];
+
+        once(0)
+            .chain(memchr_iter(DELIM, plan).map(|idx| idx + 1))
+            .zip(memchr_iter(DELIM, plan))
+            .map(move |(start, end)| (start, &plan[start..end]))
+    }
+
+    pub fn into_parts(self) -> (PlanLayout, [Box<[u8]>; 4]) {
+        let plans = match self.plans {
+            PlanBytes::Owned(plans) => plans,
+            PlanBytes::Mapped { .. } => std::array::from_fn(|plan| self[plan].into()),
+        };
+
+        (self.layout, plans)
+    }
+}
+
+impl PlanLayout {
+    pub fn directions(&self) -> DirectionSet {
+        self.directions
+    }
+
+    pub fn folding(&self) -> &Folding {
+        &self.folding
+    }
+
+    pub fn grid_hash(&self) -> u64 {
+        self.grid_hash
+    }
+
+    pub fn is_wrapping(&self) -> bool {
+        self.shape.wrapping
+    }
+
+    fn line_origin(&self, plan: usize, line: usize) -> (usize, usize) {
+        let Shape { rows, cols, .. } = self.shape;
+
+        match plan {
+            0 => (line, 0),
+            1 => (0, line),
+            _ if self.shape.wrapping => (0, line),
+            2 if line < rows => (rows - line - 1, 0),
+            2 => (0, line - rows + 1),
+            3 if line < cols => (0, line),
+            3 => (line - cols + 1, cols - 1),
+            _ => unreachable!("there are only four plans"),
+        }
+    }
+
+    /// Lengths of the lines of a plan as it's built, without their delimiters.
+    fn line_lens(&self, plan: usize) -> impl Iterator<Item = usize> + '_ {
+        let axis = Direction::FORWARD[plan];
+        let lines = match self.directions.has_axis(axis) {
+            true => self.shape.lines(axis),
+            false => 0,
+        };
+
+        (0..lines).map(move |line| match self.shape.wrapping {
+            true => 2 * self.shape.period(axis) - 1,
+            false => {
+                let origin = self.line_origin(plan, line);
+                (1..)
+                    .find(|&len| self.shape.shift_point(origin, axis, len).is_none())
+                    .unwrap()
+            }
+        })
+    }
+
+    /// Whether `bytes` hold the lines of a plan where they're built to start, each followed by a
+    /// delimiter.
+    fn is_laid_out(&self, plan: usize, bytes: &[u8]) -> bool {
+        let starts = &self.line_starts[plan];
+        let mut next = 0;
+
+        starts.len() == self.line_lens(plan).count()
+            && starts
+                .iter()
+                .zip(self.line_lens(plan))
+                .all(|(&start, len)| {
+                    let laid_out = start == next && bytes.get(start + len) == Some(&DELIM);
+                    next += len + 1;
+                    laid_out
+                })
+            && next == bytes.len()
+    }
+
+    /// Line containing `offset` of a plan, along with the offset the line starts at.
+    fn line(&self, plan: usize, offset: usize) -> (usize, usize) {
+        let starts = &self.line_starts[plan];
+        let line = starts.partition_point(|&start| start <= offset) - 1;
+
+        (line, starts[line])
+    }
+
+    /// Maps an offset within one of the plans back to the grid cell it was copied from.
+    pub fn position(&self, plan: usize, offset: usize) -> (usize, usize) {
+        let (line, start) = self.line(plan, offset);
+
+        self.shape.shift_point_unchecked(
+            self.line_origin(plan, line),
+            Direction::FORWARD[plan],
+            offset - start,
+        )
+    }
+
+    /// Whether a word of `len` found at `offset` of a plan is an occurrence of its own. Wrapping
+    /// lines repeat their cells, so only words starting within the first lap that don't overlap
+    /// themselves are.
+    pub fn is_unique(&self, plan: usize, offset: usize, len: usize) -> bool {
+        if !self.shape.wrapping {
+            return true;
+        }
+
+        let period = self.shape.period(Direction::FORWARD[plan]);
+        let (_, start) = self.line(plan, offset);
+
+        offset - start < period && len <= period
+    }
+
+    /// Converts a word found at `offset` of a plan, possibly reversed, to a [`Match`].
+    pub fn to_match(&self, plan: usize, offset: usize, len: usize, reversed: bool) -> Match {
+        Match::from_segment(
+            self.shape,
+            self.position(plan, offset),
+            Direction::FORWARD[plan],
+            len,
+            reversed,
+        )
+    }
+
+    /// Plans to search paired with whether the reversed word is searched in them.
+    pub fn searches(&self, word: &[u8]) -> impl Iterator<Item = (usize, bool)> + '_ {
+        let palindrome = is_palindrome(word);
+
+        self.directions.axes_for(word.len()).flat_map(move |axis| {
+            // Plans are laid out in the order of forward directions
+            let plan = axis as usize;
+
+            self.directions
+                .orientations(axis, palindrome)
+                .map(move |reversed| (plan, reversed))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plan_positions() {
+        for wrapping in [false, true] {
+            let crossword =
+                Crossword::new(2, b"abcdef".to_vec().into_boxed_slice()).with_wrapping(wrapping);
+            let plans = Plans::new(&crossword, DirectionSet::ALL);
+
+            for (idx, plan) in plans.iter().enumerate() {
+                for (offset, &ch) in plan.iter().enumerate() {
+                    if ch == DELIM {
+                        continue;
+                    }
+
+                    let (row, col) = plans.layout.position(idx, offset);
+                    assert_eq!(crossword.get(row, col), ch, "plan {idx}, offset {offset}");
+                }
+
+                for (start, line) in plans.lines(idx) {
+                    assert!(!line.contains(&DELIM));
+                    assert_eq!(plan[start + line.len()], DELIM);
+                }
+            }
+        }
+    }
+}
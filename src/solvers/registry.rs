@@ -0,0 +1,204 @@
+use std::{fmt, str::FromStr, sync::Arc};
+
+use crate::{Crossword, DirectionSet, EstimateSize, Solver};
+
+use super::*;
+
+/// Solvers built by name, which keep their grid shared and can be moved across threads.
+pub trait SharedSolver: Solver + EstimateSize + Send + Sync {}
+
+impl<T: Solver + EstimateSize + Send + Sync> SharedSolver for T {}
+
+type Build = fn(&Arc<Crossword>, DirectionSet, Option<usize>) -> Box<dyn SharedSolver>;
+
+/// A solver of the registry, which [`SolverSpec`] names.
+pub struct SolverEntry {
+    pub name: &'static str,
+    /// What the number following the name sets, for solvers taking one
+    pub param: Option<&'static str>,
+    build: Build,
+}
+
+/// Every solver that can be built by name.
+pub const SOLVERS: &[SolverEntry] = &[
+    SolverEntry {
+        name: "naive",
+        param: None,
+        build: |crossword, directions, _| {
+            Box::new(NaiveSolver::with_directions(crossword.clone(), directions))
+        },
+    },
+    SolverEntry {
+        name: "simd",
+        param: None,
+        build: |crossword, directions, _| {
+            Box::new(SimdSolver::with_directions(crossword.clone(), directions))
+        },
+    },
+    SolverEntry {
+        name: "bitsets",
+        param: None,
+        build: |crossword, directions, _| {
+            Box::new(CrosswordBitsets::with_directions(crossword, directions))
+        },
+    },
+    SolverEntry {
+        name: "needle",
+        param: None,
+        build: |crossword, directions, _| {
+            Box::new(CrosswordNeedleSearch::with_directions(
+                crossword, directions,
+            ))
+        },
+    },
+    SolverEntry {
+        name: "aho-corasick",
+        param: None,
+        build: |crossword, directions, _| {
+            Box::new(CrosswordAhoCorasick::with_directions(crossword, directions))
+        },
+    },
+    SolverEntry {
+        name: "fm-index",
+        param: None,
+        build: |crossword, directions, _| {
+            Box::new(CrosswordFmIndex::with_directions(crossword, directions))
+        },
+    },
+    SolverEntry {
+        name: "dawg",
+        param: None,
+        build: |crossword, directions, _| {
+            Box::new(CrosswordDawg::with_directions(crossword, directions))
+        },
+    },
+    SolverEntry {
+        name: "trie",
+        param: Some("longest words kept, all of them by default"),
+        build: |crossword, directions, limit| {
+            Box::new(Trie::with_directions(crossword, limit, directions))
+        },
+    },
+    SolverEntry {
+        name: "compact-trie",
+        param: Some("longest words kept, all of them by default"),
+        build: |crossword, directions, limit| {
+            Box::new(CompactTrie::with_directions(crossword, limit, directions))
+        },
+    },
+    SolverEntry {
+        name: "hash",
+        param: Some("length of the words indexed, 4 by default"),
+        build: |crossword, directions, word_len| {
+            Box::new(CrosswordHashMap::with_directions(
+                crossword.clone(),
+                word_len.unwrap_or(4),
+                directions,
+            ))
+        },
+    },
+];
+
+/// Name of a solver of [`SOLVERS`], followed by its parameter after a colon, e.g. `trie:14` or
+/// `hash:6`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct SolverSpec {
+    pub name: &'static str,
+    pub param: Option<usize>,
+}
+
+impl SolverSpec {
+    pub fn entry(&self) -> &'static SolverEntry {
+        SOLVERS
+            .iter()
+            .find(|entry| entry.name == self.name)
+            .expect("specs name solvers of the registry")
+    }
+
+    /// Builds the solver for a shared grid, which it may keep for as long as it lives.
+    pub fn build(
+        &self,
+        crossword: &Arc<Crossword>,
+        directions: DirectionSet,
+    ) -> Box<dyn SharedSolver> {
+        (self.entry().build)(crossword, directions, self.param)
+    }
+}
+
+impl FromStr for SolverSpec {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, param) = match s.split_once(':') {
+            Some((name, param)) => (name, Some(param)),
+            None => (s, None),
+        };
+
+        let Some(entry) = SOLVERS.iter().find(|entry| entry.name == name) else {
+            let names = SOLVERS.iter().map(|entry| entry.name).collect::<Vec<_>>();
+            anyhow::bail!(
+                "unknown solver '{name}', expected one of {}",
+                names.join(", ")
+            );
+        };
+
+        let param = match (param, entry.param) {
+            (None, _) => None,
+            (Some(_), None) => anyhow::bail!("solver '{name}' takes no parameter"),
+            (Some(param), Some(_)) => match param.parse() {
+                Ok(0) | Err(_) => anyhow::bail!("invalid parameter '{param}' of solver '{name}'"),
+                Ok(param) => Some(param),
+            },
+        };
+
+        Ok(Self {
+            name: entry.name,
+            param,
+        })
+    }
+}
+
+impl fmt::Display for SolverSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.param {
+            Some(param) => write!(f, "{}:{param}", self.name),
+            None => f.write_str(self.name),
+        }
+    }
+}
+
+/// Builds the solver `spec` names, as parsed by [`SolverSpec`], for a shared grid.
+pub fn build_solver(
+    spec: &str,
+    crossword: &Arc<Crossword>,
+    directions: DirectionSet,
+) -> anyhow::Result<Box<dyn SharedSolver>> {
+    Ok(spec.parse::<SolverSpec>()?.build(crossword, directions))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn specs_round_trip() {
+        for spec in ["naive", "trie", "trie:14", "hash:6", "compact-trie:3"] {
+            assert_eq!(spec.parse::<SolverSpec>().unwrap().to_string(), spec);
+        }
+
+        for spec in [
+            "",
+            "bogus",
+            "naive:3",
+            "hash:0",
+            "hash:",
+            "trie:x",
+            "trie:14:2",
+        ] {
+            assert!(
+                spec.parse::<SolverSpec>().is_err(),
+                "{spec} should be rejected"
+            );
+        }
+    }
+}
@@ -0,0 +1,379 @@
+use smallvec::SmallVec;
+
+use crate::{
+    utils::is_palindrome, CrosswordRef, DirectionSet, EstimateSize, Folding, Match, Shape, Solver,
+};
+
+// Cells compared at once, which fill a word of bits
+const LANES: usize = 64;
+
+/// Codes folding to a letter of a query, any of which a cell may hold to match it.
+struct Equivalents(SmallVec<[u8; 4]>);
+
+impl Equivalents {
+    fn new(folding: &Folding, code: u8) -> Self {
+        Self((0..=u8::MAX).filter(|&c| folding.fold(c) == code).collect())
+    }
+
+    fn contains(&self, code: u8) -> bool {
+        self.0.contains(&code)
+    }
+
+    /// Bit for every one of `cells` holding an equivalent code.
+    #[cfg(target_arch = "x86_64")]
+    #[inline(always)]
+    fn mask(&self, cells: &[u8; LANES]) -> u64 {
+        use std::arch::x86_64::*;
+
+        // SAFETY: SSE2 is part of the x86_64 baseline, and the loads don't need to be aligned
+        unsafe {
+            let chunks: [__m128i; 4] =
+                std::array::from_fn(|idx| _mm_loadu_si128(cells.as_ptr().add(idx * 16).cast()));
+
+            self.0.iter().fold(0, |mask, &code| {
+                let code = _mm_set1_epi8(code as i8);
+
+                chunks.iter().enumerate().fold(mask, |mask, (idx, &chunk)| {
+                    let equal = _mm_movemask_epi8(_mm_cmpeq_epi8(chunk, code)) as u16 as u64;
+                    mask | equal << (idx * 16)
+                })
+            })
+        }
+    }
+
+    /// [`Self::mask`] comparing twice as many cells per instruction.
+    #[cfg(target_arch = "x86_64")]
+    #[target_feature(enable = "avx2")]
+    fn mask_avx2(&self, cells: &[u8; LANES]) -> u64 {
+        use std::arch::x86_64::*;
+
+        // SAFETY: the loads don't need to be aligned
+        let chunks: [__m256i; 2] = std::array::from_fn(|idx| unsafe {
+            _mm256_loadu_si256(cells.as_ptr().add(idx * 32).cast())
+        });
+
+        self.0.iter().fold(0, |mask, &code| {
+            let code = _mm256_set1_epi8(code as i8);
+
+            chunks.iter().enumerate().fold(mask, |mask, (idx, &chunk)| {
+                let equal = _mm256_movemask_epi8(_mm256_cmpeq_epi8(chunk, code)) as u32 as u64;
+                mask | equal << (idx * 32)
+            })
+        })
+    }
+
+    #[cfg(not(target_arch = "x86_64"))]
+    fn mask(&self, cells: &[u8; LANES]) -> u64 {
+        self.scalar_mask(cells)
+    }
+
+    fn scalar_mask(&self, cells: &[u8]) -> u64 {
+        cells.iter().enumerate().fold(0, |mask, (idx, &cell)| {
+            mask | (self.contains(cell) as u64) << idx
+        })
+    }
+}
+
+/// Fills `bits` with the cells of `row` holding each of `letters`, a bit per cell, with the row
+/// padded to whole words.
+fn row_bits(row: &[u8], letters: &[Equivalents], bits: &mut [Vec<u64>]) {
+    #[cfg(target_arch = "x86_64")]
+    if is_x86_feature_detected!("avx2") {
+        // SAFETY: the running CPU supports AVX2
+        return unsafe { row_bits_avx2(row, letters, bits) };
+    }
+
+    fill_row_bits(row, letters, bits, Equivalents::mask)
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+fn row_bits_avx2(row: &[u8], letters: &[Equivalents], bits: &mut [Vec<u64>]) {
+    fill_row_bits(row, letters, bits, |letter, cells| letter.mask_avx2(cells))
+}
+
+#[inline(always)]
+fn fill_row_bits(
+    row: &[u8],
+    letters: &[Equivalents],
+    bits: &mut [Vec<u64>],
+    mask: impl Fn(&Equivalents, &[u8; LANES]) -> u64,
+) {
+    // The row is compared with all letters at once, while it is still cached
+    for (letter, bits) in letters.iter().zip(bits) {
+        let chunks = row.chunks_exact(LANES);
+        let rest = chunks.remainder();
+
+        bits.clear();
+        bits.extend(chunks.map(|cells| mask(letter, cells.try_into().unwrap())));
+
+        if !rest.is_empty() {
+            bits.push(letter.scalar_mask(rest));
+        }
+    }
+}
+
+/// Appends `row`, a bit per cell padded to whole words, to `target` moved so that every cell gets
+/// the bit of the cell `step` columns further, which is clear past the edges unless the grid wraps.
+fn shift_row(row: &[u64], step: isize, shape: Shape, target: &mut Vec<u64>) {
+    let last = shape.cols - 1;
+    let start = target.len();
+
+    match step {
+        1 => {
+            let next = row[1..].iter().chain([&0]);
+            target.extend(
+                row.iter()
+                    .zip(next)
+                    .map(|(&word, &next)| word >> 1 | next << 63),
+            );
+
+            if shape.wrapping {
+                target[start + last / 64] |= (row[0] & 1) << (last % 64);
+            }
+        }
+        -1 => {
+            let prev = [&0].into_iter().chain(row);
+            target.extend(
+                row.iter()
+                    .zip(prev)
+                    .map(|(&word, &prev)| word << 1 | prev >> 63),
+            );
+
+            // The last column mustn't spill into the padding
+            if !shape.cols.is_multiple_of(64) {
+                target[start + last / 64] &= (1 << (shape.cols % 64)) - 1;
+            }
+
+            if shape.wrapping {
+                target[start] |= row[last / 64] >> (last % 64) & 1;
+            }
+        }
+        _ => target.extend_from_slice(row),
+    }
+}
+
+/// Bits of a grid laid out row by row like [`row_bits`], moved with [`shift_row`].
+pub(super) fn shift(bits: &[u64], step: isize, shape: Shape) -> Vec<u64> {
+    let mut shifted = Vec::with_capacity(bits.len());
+
+    for row in bits.chunks_exact(shape.cols.div_ceil(64)) {
+        shift_row(row, step, shape, &mut shifted);
+    }
+
+    shifted
+}
+
+/// Solver without an index, which finds the cells holding the first letter of a word followed by its
+/// second one by comparing whole rows at once, and only walks these candidates. It streams the grid
+/// row by row, keeping the bits of two rows at a time.
+pub struct SimdSolver<'a> {
+    crossword: CrosswordRef<'a>,
+    directions: DirectionSet,
+    folding: Folding,
+}
+
+impl EstimateSize for SimdSolver<'_> {
+    fn estimate_size(&self) -> usize {
+        self.crossword.estimate_size()
+            + self.directions.estimate_size()
+            + self.folding.estimate_size()
+    }
+}
+
+impl<'a> SimdSolver<'a> {
+    pub fn new(crossword: impl Into<CrosswordRef<'a>>) -> Self {
+        Self::with_directions(crossword, DirectionSet::ALL)
+    }
+
+    pub fn with_directions(
+        crossword: impl Into<CrosswordRef<'a>>,
+        directions: DirectionSet,
+    ) -> Self {
+        let crossword = crossword.into();
+
+        Self {
+            directions,
+            folding: crossword.folding(),
+            crossword,
+        }
+    }
+
+    /// Reports every occurrence of `word`, which has to be folded already.
+    fn scan(&self, word: &[u8], mut report: impl FnMut(Match)) {
+        let shape = self.crossword.shape();
+        let directions = self.directions;
+        let palindrome = is_palindrome(word);
+        let reverse = word.iter().rev().copied().collect::<SmallVec<[u8; 16]>>();
+
+        let mut searches = SmallVec::<[_; 8]>::new();
+
+        for axis in directions.axes_for(word.len()) {
+            let forward = directions.contains(axis);
+            // Segments reading the same both ways are only reported forward
+            let backward = directions.contains(axis.reverse()) && !(forward && palindrome);
+
+            for (needle, reversed, searched) in [(word, false, forward), (&reverse, true, backward)]
+            {
+                if searched {
+                    searches.push((axis, needle, reversed));
+                }
+            }
+        }
+
+        // The few letters the needles start with, whose bits are shared by all directions
+        let mut letters = SmallVec::<[u8; 4]>::new();
+
+        for &code in searches
+            .iter()
+            .flat_map(|(_, needle, _)| needle.iter().take(2))
+        {
+            if !letters.contains(&code) {
+                letters.push(code);
+            }
+        }
+
+        if letters.is_empty() || shape.rows == 0 {
+            return;
+        }
+
+        let equivalents = letters
+            .iter()
+            .map(|&code| Equivalents::new(&self.folding, code))
+            .collect::<SmallVec<[_; 4]>>();
+        let letter = |code: u8| letters.iter().position(|&letter| letter == code).unwrap();
+
+        // Only the bits of a row and the one below it are kept, as no axis goes further
+        let words_per_row = shape.cols.div_ceil(64);
+        let mut current = equivalents
+            .iter()
+            .map(|_| Vec::with_capacity(words_per_row))
+            .collect::<SmallVec<[_; 4]>>();
+        let mut below = current.clone();
+        let mut second = Vec::with_capacity(words_per_row);
+
+        row_bits(self.crossword.get_row(0), &equivalents, &mut current);
+
+        for row in 0..shape.rows {
+            // The last row continues on the first one
+            match (row + 1 < shape.rows, shape.wrapping) {
+                (true, _) => row_bits(self.crossword.get_row(row + 1), &equivalents, &mut below),
+                (false, true) => row_bits(self.crossword.get_row(0), &equivalents, &mut below),
+                (false, false) => below.iter_mut().for_each(|bits| bits.fill(0)),
+            }
+
+            for &(axis, needle, reversed) in &searches {
+                let (row_step, col_step) = axis.offset();
+                let first = &current[letter(needle[0])];
+
+                // Second letters moved onto the cells before them
+                second.clear();
+
+                match needle.get(1) {
+                    Some(&code) => {
+                        let next = if row_step == 0 { &current } else { &below };
+                        shift_row(&next[letter(code)], col_step, shape, &mut second);
+                    }
+                    None => second.resize(words_per_row, u64::MAX),
+                }
+
+                for (idx, (&first, &second)) in first.iter().zip(&second).enumerate() {
+                    let mut word = first & second;
+
+                    while word != 0 {
+                        let col = idx * 64 + word.trailing_zeros() as usize;
+                        word &= word - 1;
+
+                        // The first two letters are known to match
+                        let matching = shape
+                            .shift_point((row, col), axis, needle.len() - 1)
+                            .is_some()
+                            && needle.iter().enumerate().skip(2).all(|(idx, &code)| {
+                                let (row, col) = shape.shift_point_unchecked((row, col), axis, idx);
+                                self.folding.fold(self.crossword.get(row, col)) == code
+                            });
+
+                        if matching {
+                            report(Match::from_segment(
+                                shape,
+                                (row, col),
+                                axis,
+                                needle.len(),
+                                reversed,
+                            ));
+                        }
+                    }
+                }
+            }
+
+            std::mem::swap(&mut current, &mut below);
+        }
+    }
+}
+
+impl Solver for SimdSolver<'_> {
+    fn count_occurrences(&self, word: &[u8]) -> usize {
+        if word.is_empty() {
+            return 0;
+        }
+
+        let mut count = 0;
+        self.scan(&self.folding.fold_word(word), |_| count += 1);
+
+        count
+    }
+
+    fn find_occurrences(&self, word: &[u8]) -> Vec<Match> {
+        if word.is_empty() {
+            return vec![];
+        }
+
+        let mut matches = vec![];
+        self.scan(&self.folding.fold_word(word), |m| matches.push(m));
+
+        matches
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Crossword;
+
+    #[test]
+    fn letter_masks() {
+        let folding = Folding::default();
+        let letter = Equivalents::new(&folding, b'a');
+        let mut cells = [b'x'; LANES];
+        cells[..4].copy_from_slice(b"abca");
+        cells[15] = b'a';
+        cells[63] = b'a';
+
+        let expected = 1 << 63 | 1 << 15 | 0b1001;
+
+        assert_eq!(letter.mask(&cells), expected);
+        assert_eq!(Equivalents::new(&folding, b'z').mask(&cells), 0);
+
+        #[cfg(target_arch = "x86_64")]
+        if is_x86_feature_detected!("avx2") {
+            // SAFETY: the running CPU supports AVX2
+            assert_eq!(unsafe { letter.mask_avx2(&cells) }, expected);
+        }
+
+        let row = b"a".repeat(70).into_boxed_slice();
+        let crossword = Crossword::new(1, row.clone());
+        let wrapping = Crossword::new(1, row).with_wrapping(true);
+        let mut bits = [vec![]];
+        row_bits(crossword.get_row(0), &[letter], &mut bits);
+        let [bits] = bits;
+
+        assert_eq!(bits, [u64::MAX, (1 << 6) - 1]);
+        assert_eq!(shift(&bits, 1, crossword.shape()), [u64::MAX, (1 << 5) - 1]);
+        assert_eq!(
+            shift(&bits, -1, crossword.shape()),
+            [u64::MAX - 1, (1 << 6) - 1]
+        );
+        assert_eq!(shift(&bits, 1, wrapping.shape()), bits);
+        assert_eq!(shift(&bits, -1, wrapping.shape()), bits);
+    }
+}
@@ -0,0 +1,225 @@
+use std::{collections::VecDeque, io::BufRead};
+
+use fxhash::FxHashMap as HashMap;
+use smallvec::SmallVec;
+
+use crate::{utils::is_palindrome, Alphabet, Charset, DirectionSet, EstimateSize, Normalizer};
+
+use super::{TrieNodes, TrieStorage};
+
+/// Counts of a fixed list of words in a grid read row by row, which keeps no more rows than the
+/// longest word spans, so that grids far larger than memory can be searched. Occurrences are
+/// counted at their topmost row, once every row they may reach down to has been read. Lines don't
+/// wrap, as that would take the first rows again at the end.
+pub struct StreamingCounter {
+    normalizer: Normalizer,
+    directions: DirectionSet,
+    // Normalized symbols of the words, to which those of the grid are added as they're read
+    charset: Charset,
+    nodes: TrieNodes,
+    // Words each needle counts towards along every axis
+    targets: HashMap<u32, [SmallVec<[usize; 1]>; 4]>,
+    words: usize,
+    word_len: usize,
+}
+
+impl EstimateSize for StreamingCounter {
+    fn estimate_size(&self) -> usize {
+        self.normalizer.estimate_size()
+            + self.directions.estimate_size()
+            + self.charset.estimate_size()
+            + self.nodes.estimate_size()
+            + self.targets.estimate_size()
+            + self.words.estimate_size()
+            + self.word_len.estimate_size()
+    }
+}
+
+impl StreamingCounter {
+    pub fn new(
+        words: &[&str],
+        directions: DirectionSet,
+        normalizer: Normalizer,
+    ) -> anyhow::Result<Self> {
+        let mut charset = Charset::default();
+
+        let words = words
+            .iter()
+            .map(|word| {
+                word.chars()
+                    .map(|ch| charset.insert(normalizer.normalize(ch)))
+                    .collect::<anyhow::Result<SmallVec<[u8; 16]>>>()
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        let alphabet = Alphabet::new(words.iter().flatten().copied());
+        let mut nodes = TrieNodes::new(alphabet, 1);
+        let mut targets = HashMap::<u32, [SmallVec<[usize; 1]>; 4]>::default();
+
+        for (idx, word) in words.iter().enumerate() {
+            let palindrome = is_palindrome(word);
+
+            for axis in directions.axes_for(word.len()) {
+                for reversed in directions.orientations(axis, palindrome) {
+                    let end = if reversed {
+                        nodes.insert(0, word.iter().rev().copied())
+                    } else {
+                        nodes.insert(0, word.iter().copied())
+                    };
+
+                    // The root stands for empty words, which are never counted
+                    if end != 0 {
+                        targets.entry(end).or_default()[axis as usize].push(idx);
+                    }
+                }
+            }
+        }
+
+        Ok(Self {
+            normalizer,
+            directions,
+            charset,
+            nodes,
+            targets,
+            words: words.len(),
+            word_len: words
+                .iter()
+                .map(|word| word.len())
+                .max()
+                .unwrap_or_default(),
+        })
+    }
+
+    /// Occurrences of each of the words in the grid `reader` yields rows of, one per line.
+    pub fn count(&self, reader: impl BufRead) -> anyhow::Result<Vec<usize>> {
+        let mut charset = self.charset.clone();
+        let mut codes = HashMap::<char, u8>::default();
+        let mut counts = vec![0; self.words];
+
+        let mut window = VecDeque::with_capacity(self.word_len.max(1));
+        let mut cols = None;
+
+        for line in reader.lines() {
+            let line = line?;
+
+            if line.is_empty() {
+                continue;
+            }
+
+            // Rows that have left the window are refilled instead of allocating new ones
+            let mut row = match window.len() >= self.word_len.max(1) {
+                true => {
+                    self.count_row(&window, &mut counts);
+                    window.pop_front().unwrap()
+                }
+                false => Vec::new(),
+            };
+
+            row.clear();
+
+            for ch in line.chars() {
+                let code = match codes.get(&ch) {
+                    Some(&code) => code,
+                    None => {
+                        let code = charset.insert(self.normalizer.normalize(ch))?;
+                        *codes.entry(ch).or_insert(code)
+                    }
+                };
+
+                row.push(code);
+            }
+
+            anyhow::ensure!(
+                *cols.get_or_insert(row.len()) == row.len(),
+                "inconsistent row length"
+            );
+
+            window.push_back(row);
+        }
+
+        while !window.is_empty() {
+            self.count_row(&window, &mut counts);
+            window.pop_front();
+        }
+
+        Ok(counts)
+    }
+
+    /// Counts the occurrences starting in the first row of `window`, walking down from each cell
+    /// along with the trie for as long as some needle continues.
+    fn count_row(&self, window: &VecDeque<Vec<u8>>, counts: &mut [usize]) {
+        let cols = window[0].len() as isize;
+
+        for col in 0..cols {
+            for axis in self.directions.axes() {
+                let (row_step, col_step) = axis.offset();
+                let mut node = 0;
+
+                for step in 0.. {
+                    let (row, col) = (step * row_step, col + step * col_step);
+
+                    let Some(&code) = window
+                        .get(row as usize)
+                        .filter(|_| (0..cols).contains(&col))
+                        .map(|row| &row[col as usize])
+                    else {
+                        break;
+                    };
+
+                    let Some(child) = self.nodes.child(node, code) else {
+                        break;
+                    };
+
+                    node = child;
+
+                    if let Some(targets) = self.targets.get(&node) {
+                        for &idx in &targets[axis as usize] {
+                            counts[idx] += 1;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_across_window() {
+        use crate::Direction::*;
+
+        let grid = "abca\nbcab\ncabc\n\nabca\n";
+        let words = ["abc", "cba", "a", "acb", "aa", "bcab", "cccc", ""];
+
+        let count = |directions| {
+            StreamingCounter::new(&words, directions, Normalizer::EXACT)
+                .unwrap()
+                .count(grid.as_bytes())
+                .unwrap()
+        };
+
+        assert_eq!(count(DirectionSet::ALL), [7, 7, 6, 7, 3, 2, 0, 0]);
+        assert_eq!(count(DirectionSet::from(Left)), [0, 3, 6, 3, 0, 0, 0, 0]);
+        assert_eq!(
+            count(DirectionSet::from_slice(&[Down, AntiDiagonal])),
+            [3, 0, 6, 0, 3, 1, 0, 0]
+        );
+
+        let loose = StreamingCounter::new(&["ABC", "é"], DirectionSet::ALL, Normalizer::LOOSE)
+            .unwrap()
+            .count("abc\nÉÉe".as_bytes())
+            .unwrap();
+
+        assert_eq!(loose, [1, 3]);
+
+        assert!(
+            StreamingCounter::new(&["ab"], DirectionSet::ALL, Normalizer::EXACT)
+                .unwrap()
+                .count("ab\nabc".as_bytes())
+                .is_err()
+        );
+    }
+}
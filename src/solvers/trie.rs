@@ -1,114 +1,1249 @@
-use std::iter::once;
+use std::{
+    io::{self, Read, Write},
+    iter::once,
+    sync::Arc,
+};
 
 use fxhash::FxHashMap as HashMap;
 
-use crate::{utils::is_palindrome, Crossword, Direction, EstimateSize, Solver};
+use smallvec::{smallvec, SmallVec};
 
-#[derive(Default)]
-pub struct TrieEntry {
-    count: usize,
-    children: HashMap<u8, TrieEntry>,
+use crate::{
+    persist::{invalid, IndexHeader, Persist},
+    utils::{build_sharded, is_palindrome, lines_through},
+    Alphabet, ByteClass, CellChange, Crossword, Direction, DirectionSet, EstimateSize, Folding,
+    Match, Pattern, PatternSolver, Shape, Solver, UpdatableSolver,
+};
+
+// Most nodes continue a single line, so only branching ones get a row with a slot for every symbol
+#[derive(Clone, Copy, Default)]
+enum Children {
+    #[default]
+    None,
+    Single(u8, u32),
+    Row(u32),
+}
+
+#[derive(Clone, Copy, Default)]
+struct Node {
+    count: u32,
+    children: Children,
+}
+
+impl EstimateSize for Node {
+    fn estimate_size(&self) -> usize {
+        size_of::<Self>()
+    }
+}
+
+impl Persist for Node {
+    fn persist(&self, writer: &mut dyn Write) -> io::Result<()> {
+        self.count.persist(writer)?;
+
+        match self.children {
+            Children::None => 0u8.persist(writer),
+            Children::Single(ch, child) => (1u8, ch, child).persist(writer),
+            Children::Row(row) => (2u8, row).persist(writer),
+        }
+    }
+
+    fn restore(reader: &mut dyn Read) -> io::Result<Self> {
+        let count = u32::restore(reader)?;
+        let children = match u8::restore(reader)? {
+            0 => Children::None,
+            1 => Children::Single(u8::restore(reader)?, u32::restore(reader)?),
+            2 => Children::Row(u32::restore(reader)?),
+            _ => return Err(invalid("invalid node")),
+        };
+
+        Ok(Self { count, children })
+    }
 }
 
-impl EstimateSize for TrieEntry {
+/// Nodes of a trie kept in a single arena, where children are indexed by the dense codes of an
+/// [`Alphabet`]. The first nodes are roots, so an id of 0 marks a missing child in rows.
+pub struct TrieNodes {
+    alphabet: Alphabet,
+    nodes: Vec<Node>,
+    rows: Vec<u32>,
+    origins: HashMap<u32, Vec<(usize, usize, Direction)>>,
+    // Nodes and rows no word passes through anymore, which are reused before the arena grows
+    free_nodes: Vec<u32>,
+    free_rows: Vec<u32>,
+}
+
+impl EstimateSize for TrieNodes {
     fn estimate_size(&self) -> usize {
-        self.count.estimate_size() + self.children.estimate_size()
+        self.alphabet.estimate_size()
+            + self.nodes.estimate_size()
+            + self.rows.estimate_size()
+            + self.origins.estimate_size()
+            + self.free_nodes.estimate_size()
+            + self.free_rows.estimate_size()
+    }
+}
+
+impl Persist for TrieNodes {
+    fn persist(&self, writer: &mut dyn Write) -> io::Result<()> {
+        self.alphabet.persist(writer)?;
+        self.nodes.persist(writer)?;
+        self.rows.persist(writer)?;
+        self.origins.persist(writer)
+    }
+
+    fn restore(reader: &mut dyn Read) -> io::Result<Self> {
+        let nodes = Self {
+            alphabet: Alphabet::restore(reader)?,
+            nodes: Vec::restore(reader)?,
+            rows: Vec::restore(reader)?,
+            origins: HashMap::restore(reader)?,
+            free_nodes: vec![],
+            free_rows: vec![],
+        };
+
+        nodes.validate()?;
+        Ok(nodes)
     }
 }
 
-impl TrieEntry {
-    pub fn incr(&mut self) {
-        self.count += 1;
+/// Nodes of a built trie, addressed by ids, which is all solvers need to walk it.
+pub trait TrieStorage: EstimateSize {
+    fn count(&self, node: u32) -> usize;
+
+    fn child(&self, node: u32, code: u8) -> Option<u32>;
+
+    /// Children of `node` along with the codes leading to them.
+    fn children(&self, node: u32) -> impl Iterator<Item = (u8, u32)> + '_;
+
+    /// Origins of the lines inserted through `node`, each of which starts an occurrence of the word
+    /// leading to it.
+    fn collect_origins(&self, node: u32, target: &mut Vec<(usize, usize, Direction)>);
+
+    fn get(&self, node: u32, word: &[u8]) -> Option<u32> {
+        word.iter()
+            .try_fold(node, |node, &code| self.child(node, code))
     }
 
-    pub fn decr(&mut self, by: usize) {
-        self.count = self.count.saturating_sub(by);
+    fn count_occurrences(&self, node: u32, word: &[u8]) -> usize {
+        self.get(node, word)
+            .map(|node| self.count(node))
+            .unwrap_or_default()
     }
 
-    pub fn insert(&mut self, mut word: impl Iterator<Item = u8>) {
-        let Some(ch) = word.next() else {
+    /// Nodes reached by words matching `classes`, branching over all accepted children.
+    fn get_matching(&self, node: u32, classes: &[ByteClass], target: &mut Vec<u32>) {
+        let Some((class, rest)) = classes.split_first() else {
+            target.push(node);
             return;
         };
 
-        let child = self.children.entry(ch).or_default();
+        for (ch, child) in self.children(node) {
+            if class.contains(ch) {
+                self.get_matching(child, rest, target);
+            }
+        }
+    }
+
+    fn count_matching(&self, node: u32, classes: &[ByteClass]) -> usize {
+        let Some((class, rest)) = classes.split_first() else {
+            return self.count(node);
+        };
+
+        self.children(node)
+            .filter(|&(ch, _)| class.contains(ch))
+            .map(|(_, child)| self.count_matching(child, rest))
+            .sum()
+    }
+
+    /// Counts occurrences of each of `words` below `node`, which have to be sorted so that shared
+    /// prefixes are only walked once.
+    fn count_sorted(&self, node: u32, words: &[&[u8]]) -> Vec<usize> {
+        // Nodes along the longest walkable prefix of the previous word
+        let mut path = vec![node];
+        let mut previous: &[u8] = &[];
+
+        words
+            .iter()
+            .map(|word| {
+                let common = previous
+                    .iter()
+                    .zip(word.iter())
+                    .take_while(|(a, b)| a == b)
+                    .count();
+
+                path.truncate(common.min(path.len() - 1) + 1);
+                previous = word;
 
-        child.incr();
-        child.insert(word);
+                while path.len() <= word.len() {
+                    let Some(child) = self.child(*path.last().unwrap(), word[path.len() - 1])
+                    else {
+                        return 0;
+                    };
+
+                    path.push(child);
+                }
+
+                self.count(*path.last().unwrap())
+            })
+            .collect()
     }
+}
 
-    pub fn count_occurrences(&self, word: &[u8]) -> usize {
-        if word.is_empty() {
-            return self.count;
+impl TrieNodes {
+    pub fn new(alphabet: Alphabet, roots: usize) -> Self {
+        Self {
+            alphabet,
+            nodes: vec![Node::default(); roots],
+            rows: vec![],
+            origins: HashMap::default(),
+            free_nodes: vec![],
+            free_rows: vec![],
         }
+    }
+
+    /// Adds `codes` to the alphabet children are indexed by, laying every row out again when some
+    /// of them are new.
+    pub fn extend_alphabet(&mut self, codes: impl IntoIterator<Item = u8>) {
+        let alphabet = Alphabet::new(self.alphabet.codes().iter().copied().chain(codes));
+        let (old_len, len) = (self.alphabet.len(), alphabet.len());
 
-        match self.children.get(&word[0]) {
-            Some(child) => child.count_occurrences(&word[1..]),
-            None => 0,
+        if len == old_len {
+            return;
         }
+
+        let moved = |dense: u8| alphabet.dense(self.alphabet.code(dense)).unwrap();
+        let mut rows = vec![0; self.rows.len() / old_len.max(1) * len];
+
+        for (row, children) in self.rows.chunks(old_len.max(1)).enumerate() {
+            for (dense, &child) in (0..).zip(children) {
+                rows[row * len + moved(dense) as usize] = child;
+            }
+        }
+
+        for node in &mut self.nodes {
+            if let Children::Single(ch, child) = node.children {
+                node.children = Children::Single(moved(ch), child);
+            }
+        }
+
+        self.rows = rows;
+        self.alphabet = alphabet;
+    }
+
+    /// Checks that restored nodes only link to nodes and rows there are, so that reading them
+    /// doesn't panic.
+    fn validate(&self) -> io::Result<()> {
+        let len = self.alphabet.len();
+        let rows = self.rows.len().checked_div(len).unwrap_or_default();
+
+        if self.rows.len() != rows * len {
+            return Err(invalid("rows don't match the alphabet"));
+        }
+
+        for entry in &self.nodes {
+            let valid = |child: u32| child != 0 && (child as usize) < self.nodes.len();
+
+            let linked = match entry.children {
+                Children::None => true,
+                Children::Single(ch, child) => (ch as usize) < len && valid(child),
+                Children::Row(row) => {
+                    (row as usize) < rows
+                        && self
+                            .row(row)
+                            .iter()
+                            .all(|&child| child == 0 || valid(child))
+                }
+            };
+
+            if !linked {
+                return Err(invalid("invalid child"));
+            }
+        }
+
+        if self
+            .origins
+            .keys()
+            .any(|&node| node as usize >= self.nodes.len())
+        {
+            return Err(invalid("origins of a missing node"));
+        }
+
+        Ok(())
+    }
+
+    /// Walks restored nodes from `roots`, failing when a node is reached twice, which built tries
+    /// never share and walks would loop on, and reuses the nodes and rows that aren't reached.
+    pub(crate) fn reclaim(&mut self, roots: &[u32]) -> io::Result<()> {
+        let mut reached = vec![false; self.nodes.len()];
+        let mut used_rows = vec![false; self.rows.len() / self.alphabet.len().max(1)];
+        let mut stack = roots.to_vec();
+
+        while let Some(node) = stack.pop() {
+            if std::mem::replace(&mut reached[node as usize], true) {
+                return Err(invalid("nodes reached twice"));
+            }
+
+            if let Children::Row(row) = self.nodes[node as usize].children {
+                used_rows[row as usize] = true;
+            }
+
+            stack.extend(self.children(node).map(|(_, child)| child));
+        }
+
+        self.free_nodes = (0..)
+            .zip(&reached)
+            .filter(|&(_, &r)| !r)
+            .map(|(node, _)| node)
+            .collect();
+        self.free_rows = (0..)
+            .zip(&used_rows)
+            .filter(|&(_, &u)| !u)
+            .map(|(row, _)| row)
+            .collect();
+
+        for &node in &self.free_nodes {
+            self.nodes[node as usize] = Node::default();
+            self.origins.remove(&node);
+        }
+
+        let len = self.alphabet.len();
+        for &row in &self.free_rows {
+            self.rows[row as usize * len..(row as usize + 1) * len].fill(0);
+        }
+
+        Ok(())
+    }
+
+    fn row(&self, row: u32) -> &[u32] {
+        let len = self.alphabet.len();
+        &self.rows[row as usize * len..(row as usize + 1) * len]
+    }
+
+    fn child_or_insert(&mut self, node: u32, code: u8) -> u32 {
+        let dense = self
+            .alphabet
+            .dense(code)
+            .expect("symbols are inserted from the alphabet");
+        let len = self.alphabet.len();
+        let next = (self.free_nodes.last().copied()).unwrap_or(self.nodes.len() as u32);
+
+        let child = match self.nodes[node as usize].children {
+            Children::Single(ch, child) if ch == dense => return child,
+            Children::Row(row) => {
+                let slot = &mut self.rows[row as usize * len + dense as usize];
+
+                if *slot != 0 {
+                    return *slot;
+                }
+
+                *slot = next;
+                Children::Row(row)
+            }
+            Children::Single(ch, child) => {
+                let row = self.free_rows.pop().unwrap_or_else(|| {
+                    self.rows.resize(self.rows.len() + len, 0);
+                    (self.rows.len() / len - 1) as u32
+                });
+
+                self.rows[row as usize * len + ch as usize] = child;
+                self.rows[row as usize * len + dense as usize] = next;
+
+                Children::Row(row)
+            }
+            Children::None => Children::Single(dense, next),
+        };
+
+        self.nodes[node as usize].children = child;
+
+        // Freed nodes were left empty
+        if self.free_nodes.pop().is_none() {
+            self.nodes.push(Node::default());
+        }
+
+        next
+    }
+
+    /// Inserts `word` below `node`, returning the node it ends at.
+    pub fn insert(&mut self, mut node: u32, word: impl Iterator<Item = u8>) -> u32 {
+        for code in word {
+            node = self.child_or_insert(node, code);
+            self.nodes[node as usize].count += 1;
+        }
+
+        node
+    }
+
+    /// Takes back an insertion of `word` below `node`, returning the node it ends at, or `None`
+    /// when no word passes through it anymore. Such nodes are unlinked from their parents and
+    /// freed along with everything below them.
+    pub fn remove(&mut self, mut node: u32, word: impl Iterator<Item = u8>) -> Option<u32> {
+        for code in word {
+            let child = self.child(node, code);
+            debug_assert!(child.is_some(), "only inserted words are removed");
+
+            let child = child?;
+            self.decr(child, 1);
+
+            if self.nodes[child as usize].count == 0 {
+                self.unlink(node, child);
+                self.free(child);
+
+                return None;
+            }
+
+            node = child;
+        }
+
+        Some(node)
+    }
+
+    pub fn incr(&mut self, node: u32, by: usize) {
+        self.nodes[node as usize].count += by as u32;
+    }
+
+    pub fn decr(&mut self, node: u32, by: usize) {
+        let count = &mut self.nodes[node as usize].count;
+        debug_assert!(*count >= by as u32, "only inserted words are removed");
+
+        *count = count.saturating_sub(by as u32);
+    }
+
+    fn unlink(&mut self, node: u32, child: u32) {
+        match self.nodes[node as usize].children {
+            Children::Single(_, only) if only == child => {
+                self.nodes[node as usize].children = Children::None;
+            }
+            Children::Row(row) => {
+                let len = self.alphabet.len();
+                let children = &mut self.rows[row as usize * len..(row as usize + 1) * len];
+
+                if let Some(slot) = children.iter_mut().find(|slot| **slot == child) {
+                    *slot = 0;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Frees `node` and everything below it, which no word passes through anymore
+    fn free(&mut self, node: u32) {
+        let mut stack = vec![node];
+
+        while let Some(node) = stack.pop() {
+            stack.extend(self.children(node).map(|(_, child)| child));
+
+            if let Children::Row(row) = self.nodes[node as usize].children {
+                let len = self.alphabet.len();
+                self.rows[row as usize * len..(row as usize + 1) * len].fill(0);
+                self.free_rows.push(row);
+            }
+
+            self.nodes[node as usize] = Node::default();
+            self.origins.remove(&node);
+            self.free_nodes.push(node);
+        }
+    }
+
+    pub fn add_origin(&mut self, node: u32, origin: (usize, usize, Direction)) {
+        self.origins.entry(node).or_default().push(origin);
+    }
+
+    pub fn remove_origin(&mut self, node: u32, origin: (usize, usize, Direction)) {
+        let Some(origins) = self.origins.get_mut(&node) else {
+            return;
+        };
+
+        if let Some(idx) = origins.iter().position(|&other| other == origin) {
+            origins.swap_remove(idx);
+        }
+
+        if origins.is_empty() {
+            self.origins.remove(&node);
+        }
+    }
+
+    // Inserts a whole line read from `origin`, or removes it again
+    fn update_line(
+        &mut self,
+        root: u32,
+        word: impl Iterator<Item = u8>,
+        origin: (usize, usize, Direction),
+        removed: bool,
+    ) {
+        if removed {
+            // Freed nodes lose their origins along with them
+            if let Some(end) = self.remove(root, word) {
+                self.remove_origin(end, origin);
+            }
+        } else {
+            let end = self.insert(root, word);
+            self.add_origin(end, origin);
+        }
+    }
+
+    /// Adds the words of `other`, which has the same alphabet and `roots`, walking the smaller of
+    /// the two into the larger one.
+    pub fn merge<const N: usize>(self, other: Self, roots: [u32; N]) -> Self {
+        let (mut into, from) = if self.nodes.len() >= other.nodes.len() {
+            (self, other)
+        } else {
+            (other, self)
+        };
+
+        for root in roots {
+            into.merge_node(root, &from, root);
+        }
+
+        into
+    }
+
+    fn merge_node(&mut self, node: u32, other: &Self, other_node: u32) {
+        for &origin in other.origins(other_node) {
+            self.add_origin(node, origin);
+        }
+
+        for (code, other_child) in other.children(other_node) {
+            let child = self.child_or_insert(node, code);
+
+            self.nodes[child as usize].count += other.nodes[other_child as usize].count;
+            self.merge_node(child, other, other_child);
+        }
+    }
+
+    /// Origins of the lines inserted as a whole up to `node`.
+    pub fn origins(&self, node: u32) -> &[(usize, usize, Direction)] {
+        self.origins
+            .get(&node)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    // Words inserted as a whole end here, unless every one of them continues into a child
+    fn ends_word(&self, node: u32) -> bool {
+        self.count(node)
+            > self
+                .children(node)
+                .map(|(_, child)| self.count(child))
+                .sum()
     }
 }
 
-pub struct Trie {
-    root: TrieEntry,
+impl TrieStorage for TrieNodes {
+    fn count(&self, node: u32) -> usize {
+        self.nodes[node as usize].count as usize
+    }
+
+    fn child(&self, node: u32, code: u8) -> Option<u32> {
+        let dense = self.alphabet.dense(code)?;
+
+        match self.nodes[node as usize].children {
+            Children::None => None,
+            Children::Single(ch, child) => (ch == dense).then_some(child),
+            Children::Row(row) => Some(self.row(row)[dense as usize]).filter(|&child| child != 0),
+        }
+    }
+
+    fn children(&self, node: u32) -> impl Iterator<Item = (u8, u32)> + '_ {
+        let (single, row) = match self.nodes[node as usize].children {
+            Children::None => (None, &[][..]),
+            Children::Single(ch, child) => (Some((ch, child)), &[][..]),
+            Children::Row(row) => (None, self.row(row)),
+        };
+
+        let row = (0..)
+            .zip(row.iter().copied())
+            .filter(|&(_, child)| child != 0);
+
+        single
+            .into_iter()
+            .chain(row)
+            .map(|(ch, child)| (self.alphabet.code(ch), child))
+    }
+
+    fn collect_origins(&self, node: u32, target: &mut Vec<(usize, usize, Direction)>) {
+        target.extend_from_slice(self.origins(node));
+
+        for (_, child) in self.children(node) {
+            self.collect_origins(child, target);
+        }
+    }
+}
+
+// Roots of the nodes of a `Trie` as it's built
+const MIRRORED: u32 = 0;
+const DIRECTED: u32 = 1;
+
+/// Inserts the lines read from `(row, col)` into both roots, as far as they go up to the limit, or
+/// removes them again when `removed` is set. Cells are read through `cell`, already folded.
+fn insert_cell(
+    nodes: &mut TrieNodes,
+    shape: Shape,
+    directions: DirectionSet,
+    word_len_limit: Option<usize>,
+    (row, col): (usize, usize),
+    cell: &impl Fn((usize, usize)) -> u8,
+    removed: bool,
+) {
+    let central_char = cell((row, col));
+
+    for (root, mirrored) in [(MIRRORED, true), (DIRECTED, false)] {
+        let lines = directions
+            .axes()
+            .filter(|&axis| directions.is_mirrored(axis) == mirrored)
+            .map(|axis| {
+                let dir = directions.orient(axis);
+                let max_len = shape
+                    .line_len(axis)
+                    .min(word_len_limit.unwrap_or(usize::MAX));
+
+                (2..=max_len)
+                    .rev()
+                    .find(|&len| shape.shift_point((row, col), dir, len - 1).is_some())
+                    .map(|len| (dir, len))
+            })
+            .collect::<SmallVec<[_; 4]>>();
+
+        if lines.is_empty() {
+            continue;
+        }
+
+        let valid_dirs = lines.iter().flatten().count();
+
+        if valid_dirs == 0 {
+            let origin = (row, col, directions.orient(Direction::Right));
+            nodes.update_line(root, once(central_char), origin, removed);
+            continue;
+        }
+
+        // Prevent central character from being added more than once, which is undone before its
+        // lines are removed so that its count never drops below the ones of other cells
+        if removed && valid_dirs > 1 {
+            let child = nodes.child(root, central_char).unwrap();
+            nodes.incr(child, valid_dirs - 1);
+        }
+
+        for &(dir, len) in lines.iter().flatten() {
+            let word = (0..len).map(|k| cell(shape.shift_point_unchecked((row, col), dir, k)));
+            nodes.update_line(root, word, (row, col, dir), removed);
+        }
+
+        if !removed && valid_dirs > 1 {
+            let child = nodes.child(root, central_char).unwrap();
+            nodes.decr(child, valid_dirs - 1);
+        }
+    }
 }
 
-impl EstimateSize for Trie {
+pub struct Trie<N = TrieNodes> {
+    shape: Shape,
+    directions: DirectionSet,
+    folding: Folding,
+    word_len_limit: Option<usize>,
+    // Fingerprint of the grid the trie was built from, which saved tries are checked against
+    grid_hash: u64,
+    nodes: N,
+    // Lines readable both ways, stored in the forward direction
+    mirrored: u32,
+    // Lines readable in a single direction, stored as read
+    directed: u32,
+}
+
+impl<N: EstimateSize> EstimateSize for Trie<N> {
     fn estimate_size(&self) -> usize {
-        self.root.estimate_size()
+        self.shape.estimate_size()
+            + self.directions.estimate_size()
+            + self.folding.estimate_size()
+            + size_of::<Option<usize>>()
+            + self.grid_hash.estimate_size()
+            + self.nodes.estimate_size()
+            + self.mirrored.estimate_size()
+            + self.directed.estimate_size()
     }
 }
 
 impl Trie {
     pub fn new(crossword: &Crossword, word_len_limit: Option<usize>) -> Self {
-        let mut root = TrieEntry::default();
+        Self::with_directions(crossword, word_len_limit, DirectionSet::ALL)
+    }
+
+    pub fn with_directions(
+        crossword: &Crossword,
+        word_len_limit: Option<usize>,
+        directions: DirectionSet,
+    ) -> Self {
+        let shape = crossword.shape();
+        let folding = crossword.folding();
+        let alphabet = crossword.alphabet();
+
+        let nodes = build_sharded(
+            crossword.rows(),
+            || TrieNodes::new(alphabet.clone(), 2),
+            |nodes, row| {
+                for col in 0..crossword.cols() {
+                    insert_cell(
+                        nodes,
+                        shape,
+                        directions,
+                        word_len_limit,
+                        (row, col),
+                        &|(row, col)| folding.fold(crossword.get(row, col)),
+                        false,
+                    );
+                }
+            },
+            |nodes, other| nodes.merge(other, [MIRRORED, DIRECTED]),
+        );
+
+        Self {
+            shape,
+            directions,
+            folding,
+            word_len_limit,
+            grid_hash: crossword.grid_hash(),
+            nodes,
+            mirrored: MIRRORED,
+            directed: DIRECTED,
+        }
+    }
+
+    /// Writes the trie as an index file, which [`Trie::load`] reads back for the same grid.
+    pub fn save(&self, mut writer: impl Write) -> io::Result<()> {
+        let header = IndexHeader {
+            solver: "trie".into(),
+            word_len: self.word_len_limit,
+            directions: self.directions,
+            grid_hash: self.grid_hash,
+        };
+
+        header.write(&mut writer)?;
+        self.nodes.persist(&mut writer)?;
+        (self.mirrored, self.directed).persist(&mut writer)
+    }
+
+    /// Reads a trie saved by [`Trie::save`], failing when it was built for another grid than
+    /// `crossword`, so that it's never searched with a mismatched one.
+    pub fn load(mut reader: impl Read, crossword: &Crossword) -> anyhow::Result<Self> {
+        let header = IndexHeader::read_for(&mut reader, "trie", crossword)?;
+        let mut nodes = TrieNodes::restore(&mut reader)?;
+        let (mirrored, directed): (u32, u32) = Persist::restore(&mut reader)?;
+        let shape = crossword.shape();
+
+        anyhow::ensure!(
+            [mirrored, directed]
+                .iter()
+                .all(|&root| (root as usize) < nodes.nodes.len()),
+            "invalid roots"
+        );
+        nodes.reclaim(&[mirrored, directed])?;
+        anyhow::ensure!(
+            nodes
+                .origins
+                .values()
+                .flatten()
+                .all(|&(row, col, _)| row < shape.rows && col < shape.cols),
+            "origins outside the grid"
+        );
+
+        Ok(Self {
+            shape: crossword.shape(),
+            directions: header.directions,
+            folding: crossword.folding(),
+            word_len_limit: header.word_len,
+            grid_hash: header.grid_hash,
+            nodes,
+            mirrored,
+            directed,
+        })
+    }
+
+    /// Same trie with its nodes moved to other storage by `convert`, which is given the roots and
+    /// returns where they ended up.
+    pub(crate) fn map_nodes<M>(
+        self,
+        convert: impl FnOnce(TrieNodes, [u32; 2]) -> (M, [u32; 2]),
+    ) -> Trie<M> {
+        let (nodes, [mirrored, directed]) = convert(self.nodes, [self.mirrored, self.directed]);
+
+        Trie {
+            shape: self.shape,
+            directions: self.directions,
+            folding: self.folding,
+            word_len_limit: self.word_len_limit,
+            grid_hash: self.grid_hash,
+            nodes,
+            mirrored,
+            directed,
+        }
+    }
+}
+
+impl UpdatableSolver for Trie {
+    fn update(&mut self, crossword: &Arc<Crossword>, changes: &[CellChange]) {
+        assert_eq!(
+            crossword.shape(),
+            self.shape,
+            "grids can't change their shape"
+        );
+
+        // Cells changed several times are read as they were before the first change
+        let mut old = HashMap::default();
+
+        for change in changes {
+            old.entry((change.row, change.col)).or_insert(change.old);
+        }
+
+        let limit = self.word_len_limit.unwrap_or(usize::MAX);
+        let starts = lines_through(self.shape, self.directions, changes, |axis| {
+            self.shape.line_len(axis).min(limit)
+        });
+
+        let folding = &self.folding;
+        let before = |(row, col)| {
+            folding.fold(
+                old.get(&(row, col))
+                    .copied()
+                    .unwrap_or(crossword.get(row, col)),
+            )
+        };
+        let after = |(row, col)| folding.fold(crossword.get(row, col));
+
+        self.nodes
+            .extend_alphabet(changes.iter().map(|change| folding.fold(change.new)));
+
+        for &start in &starts {
+            insert_cell(
+                &mut self.nodes,
+                self.shape,
+                self.directions,
+                self.word_len_limit,
+                start,
+                &before,
+                true,
+            );
+        }
+
+        for &start in &starts {
+            insert_cell(
+                &mut self.nodes,
+                self.shape,
+                self.directions,
+                self.word_len_limit,
+                start,
+                &after,
+                false,
+            );
+        }
+
+        self.grid_hash = crossword.grid_hash();
+    }
+}
+
+impl<N: TrieStorage> Trie<N> {
+    fn primary_root(&self) -> u32 {
+        let mirrored = self
+            .directions
+            .primary()
+            .is_some_and(|dir| self.directions.is_mirrored(dir.axis()));
+
+        if mirrored {
+            self.mirrored
+        } else {
+            self.directed
+        }
+    }
+
+    /// Roots to look `word` up in, paired with whether the reversed word is looked up.
+    fn lookups(&self, word: &[u8]) -> SmallVec<[(u32, bool); 3]> {
+        if word.len() == 1 {
+            // Each cell is present once in both roots, so only the one of the primary direction
+            // is asked
+            return smallvec![(self.primary_root(), false)];
+        }
+
+        let mut lookups = smallvec![(self.mirrored, false), (self.directed, false)];
+
+        if !is_palindrome(word) {
+            lookups.push((self.mirrored, true));
+        }
+
+        lookups
+    }
+}
+
+impl<N: TrieStorage> Solver for Trie<N> {
+    fn count_occurrences(&self, word: &[u8]) -> usize {
+        if word.is_empty() {
+            return 0;
+        }
+
+        let word: &[u8] = &self.folding.fold_word(word);
+        let reverse = word.iter().rev().copied().collect::<SmallVec<[u8; 16]>>();
+
+        self.lookups(word)
+            .into_iter()
+            .map(|(root, reversed)| {
+                self.nodes
+                    .count_occurrences(root, if reversed { &reverse } else { word })
+            })
+            .sum()
+    }
+
+    fn count_many(&self, words: &[&[u8]]) -> Vec<usize> {
+        let folded = words
+            .iter()
+            .map(|word| self.folding.fold_word(word))
+            .collect::<Vec<_>>();
+        let words = folded.iter().map(|word| word.as_ref()).collect::<Vec<_>>();
+
+        let reverses = words
+            .iter()
+            .map(|word| word.iter().rev().copied().collect::<SmallVec<[u8; 16]>>())
+            .collect::<Vec<_>>();
+
+        let mut counts = vec![0; words.len()];
+
+        for root in [self.mirrored, self.directed] {
+            let mut queries = words
+                .iter()
+                .enumerate()
+                .filter(|(_, word)| !word.is_empty())
+                .flat_map(|(idx, word)| {
+                    self.lookups(word)
+                        .into_iter()
+                        .filter(|&(lookup, _)| lookup == root)
+                        .map(move |(_, reversed)| (idx, reversed))
+                })
+                .map(|(idx, reversed)| {
+                    let needle = if reversed {
+                        &reverses[idx][..]
+                    } else {
+                        words[idx]
+                    };
+
+                    (needle, idx)
+                })
+                .collect::<Vec<_>>();
+
+            queries.sort_unstable();
+
+            let needles = queries
+                .iter()
+                .map(|&(needle, _)| needle)
+                .collect::<Vec<_>>();
 
-        let max_len = word_len_limit.unwrap_or(crossword.rows().max(crossword.cols()));
+            for ((_, idx), count) in queries.iter().zip(self.nodes.count_sorted(root, &needles)) {
+                counts[*idx] += count;
+            }
+        }
+
+        counts
+    }
+
+    fn find_occurrences(&self, word: &[u8]) -> Vec<Match> {
+        if word.is_empty() {
+            return vec![];
+        }
+
+        let word: &[u8] = &self.folding.fold_word(word);
+        let reverse = word.iter().rev().copied().collect::<SmallVec<[u8; 16]>>();
+        let mut matches = vec![];
+
+        for (root, reversed) in self.lookups(word) {
+            let mut origins = vec![];
+
+            if let Some(node) = self.nodes.get(root, if reversed { &reverse } else { word }) {
+                self.nodes.collect_origins(node, &mut origins);
+            }
+
+            matches.extend(origins.into_iter().map(|(row, col, dir)| {
+                Match::from_segment(self.shape, (row, col), dir, word.len(), reversed)
+            }));
+        }
+
+        // Same as in construction, a single letter is only reported once per cell
+        if word.len() == 1 {
+            for m in &mut matches {
+                m.dir = self.directions.primary().unwrap();
+            }
+
+            matches.sort_unstable();
+            matches.dedup();
+        }
+
+        matches
+    }
+}
+
+/// Words to look for in a grid all at once, sharing their prefixes in a trie.
+pub struct Dictionary {
+    nodes: TrieNodes,
+}
+
+impl EstimateSize for Dictionary {
+    fn estimate_size(&self) -> usize {
+        self.nodes.estimate_size()
+    }
+}
+
+impl Dictionary {
+    pub fn new<W: AsRef<[u8]>>(words: impl IntoIterator<Item = W>) -> Self {
+        let words = words.into_iter().collect::<Vec<_>>();
+        let alphabet = Alphabet::new(words.iter().flat_map(|word| word.as_ref().iter().copied()));
+        let mut nodes = TrieNodes::new(alphabet, 1);
+
+        for word in &words {
+            nodes.insert(0, word.as_ref().iter().copied());
+        }
+
+        Self { nodes }
+    }
+
+    /// Every occurrence of a dictionary word, found by walking the lines from each cell along with
+    /// the trie until no word continues the path. Palindromes and single letters are reported once,
+    /// the same way solvers count them. Words are expected as [`Crossword::encode`] returns them.
+    pub fn scan(&self, crossword: &Crossword, directions: DirectionSet) -> Vec<Match> {
+        let shape = crossword.shape();
+        let folding = crossword.folding();
+        let primary = directions.primary();
+
+        let mut matches = vec![];
+        let mut path = SmallVec::<[u8; 16]>::new();
 
         for row in 0..crossword.rows() {
             for col in 0..crossword.cols() {
-                let mut valid_dirs = 0;
-                let central_char = crossword.get(row, col);
+                for dir in directions.iter() {
+                    let mut node = 0;
+                    path.clear();
 
-                for dir in Direction::ALL {
-                    for len in (2..=max_len).rev() {
-                        let Some(word) = crossword.get_word(row, col, dir, len) else {
+                    while let Some((r, c)) = shape.shift_point((row, col), dir, path.len()) {
+                        let ch = folding.fold(crossword.get(r, c));
+
+                        let Some(child) = self.nodes.child(node, ch) else {
+                            break;
+                        };
+
+                        node = child;
+                        path.push(ch);
+
+                        if !self.nodes.ends_word(node) {
                             continue;
+                        }
+
+                        let duplicate = match path.len() {
+                            1 => Some(dir) != primary,
+                            _ => {
+                                !dir.is_forward()
+                                    && directions.contains(dir.reverse())
+                                    && is_palindrome(&path)
+                            }
                         };
 
-                        valid_dirs += 1;
-                        root.insert(word);
-                        break;
+                        if !duplicate {
+                            matches.push(Match {
+                                row,
+                                col,
+                                dir,
+                                len: path.len(),
+                            });
+                        }
                     }
                 }
+            }
+        }
 
-                // Prevent central character from being added more than once
-                if valid_dirs > 1 {
-                    root.children
-                        .get_mut(&central_char)
-                        .unwrap()
-                        .decr(valid_dirs - 1);
-                } else if valid_dirs == 0 {
-                    root.insert(once(central_char));
-                }
+        matches
+    }
+}
+
+fn matching_origins(
+    nodes: &impl TrieStorage,
+    root: u32,
+    pattern: &Pattern,
+) -> Vec<(usize, usize, Direction)> {
+    let mut matching = vec![];
+    let mut origins = vec![];
+
+    nodes.get_matching(root, pattern.classes(), &mut matching);
+
+    for node in matching {
+        nodes.collect_origins(node, &mut origins);
+    }
+
+    origins
+}
+
+/// Nodes below `node` whose words start with `classes`, or end with them reversed when `backward`
+/// is set and they don't start with them too, paired with their depth and whether they were read
+/// backward.
+///
+/// Words ending with a pattern can continue from any letter, so backward walks visit every node
+/// below `node`, while forward ones skip the subtrees of the letters that don't match.
+fn prefixed_nodes(
+    nodes: &impl TrieStorage,
+    node: u32,
+    classes: &[ByteClass],
+    backward: bool,
+    target: &mut Vec<(u32, usize, bool)>,
+) {
+    let len = classes.len();
+    let starts = |path: &[u8]| {
+        path.iter()
+            .zip(classes)
+            .all(|(&ch, class)| class.contains(ch))
+    };
+
+    // Walked iteratively, as tries get as deep as the longest lines
+    let mut path = vec![];
+    let mut stack = vec![(node, 0, None)];
+
+    // Nodes come with the length of their parent's path and the letter leading to them
+    while let Some((node, parent_len, ch)) = stack.pop() {
+        path.truncate(parent_len);
+        path.extend(ch);
+
+        if !backward && !starts(&path) {
+            // The whole subtree shares the letters that don't match
+            continue;
+        }
+
+        if path.len() >= len {
+            if starts(&path) {
+                target.push((node, path.len(), false));
+            } else if path
+                .iter()
+                .rev()
+                .zip(classes)
+                .all(|(&ch, class)| class.contains(ch))
+            {
+                target.push((node, path.len(), true));
             }
         }
 
-        Self { root }
+        stack.extend(
+            nodes
+                .children(node)
+                .map(|(ch, child)| (child, path.len(), Some(ch))),
+        );
     }
 }
 
-impl Solver for Trie {
-    fn count_occurrences(&self, word: &[u8]) -> usize {
-        if word.is_empty() {
-            return 0;
+impl<N: TrieStorage> Trie<N> {
+    /// Nodes of the words matching a prefix pattern, see [`prefixed_nodes`].
+    fn prefixed(&self, pattern: &Pattern) -> Vec<(u32, usize, bool)> {
+        let primary = self.primary_root();
+        let mut found = vec![];
+
+        for (root, backward) in [(self.directed, false), (self.mirrored, true)] {
+            let mut nodes = vec![];
+            prefixed_nodes(&self.nodes, root, pattern.classes(), backward, &mut nodes);
+
+            // Each cell is present once in both roots, so single letters come from the primary one
+            found.extend(
+                nodes
+                    .into_iter()
+                    .filter(|&(_, depth, _)| root == primary || depth > 1),
+            );
         }
 
-        if is_palindrome(word) {
-            self.root.count_occurrences(word)
-        } else {
-            let reverse = word.iter().rev().copied().collect::<Vec<_>>();
+        found
+    }
+}
 
-            self.root.count_occurrences(word) + self.root.count_occurrences(&reverse)
+impl<N: TrieStorage> PatternSolver for Trie<N> {
+    fn count_pattern(&self, pattern: &Pattern) -> usize {
+        let pattern = &self.folding.fold_pattern(pattern);
+
+        if pattern.is_prefix() {
+            return self
+                .prefixed(pattern)
+                .into_iter()
+                .map(|(node, _, _)| self.nodes.count(node))
+                .sum();
         }
+
+        if pattern.len() == 1 {
+            return self
+                .nodes
+                .count_matching(self.primary_root(), pattern.classes());
+        }
+
+        let reversed = pattern.reversed();
+
+        // Lines matching both ways are only counted once
+        self.nodes.count_matching(self.directed, pattern.classes())
+            + self.nodes.count_matching(self.mirrored, pattern.classes())
+            + self.nodes.count_matching(self.mirrored, reversed.classes())
+            - pattern
+                .intersect(&reversed)
+                .map(|both| self.nodes.count_matching(self.mirrored, both.classes()))
+                .unwrap_or_default()
+    }
+
+    fn find_pattern(&self, pattern: &Pattern) -> Vec<Match> {
+        let pattern = &self.folding.fold_pattern(pattern);
+        let len = pattern.len();
+
+        if pattern.is_prefix() {
+            let mut origins = vec![];
+            let mut matches = vec![];
+
+            for (node, len, reversed) in self.prefixed(pattern) {
+                origins.clear();
+                self.nodes.collect_origins(node, &mut origins);
+
+                matches.extend(origins.iter().map(|&(row, col, dir)| match len {
+                    1 => Match {
+                        row,
+                        col,
+                        dir: self.directions.primary().unwrap(),
+                        len,
+                    },
+                    _ => Match::from_segment(self.shape, (row, col), dir, len, reversed),
+                }));
+            }
+
+            matches.sort_unstable();
+            matches.dedup();
+
+            return matches;
+        }
+
+        if len == 1 {
+            let dir = self.directions.primary();
+            let mut matches = matching_origins(&self.nodes, self.primary_root(), pattern)
+                .into_iter()
+                .map(|(row, col, _)| Match {
+                    row,
+                    col,
+                    dir: dir.unwrap(),
+                    len,
+                })
+                .collect::<Vec<_>>();
+
+            matches.sort_unstable();
+            matches.dedup();
+
+            return matches;
+        }
+
+        let reversed = pattern.reversed();
+        let mut both = pattern
+            .intersect(&reversed)
+            .map(|both| matching_origins(&self.nodes, self.mirrored, &both))
+            .unwrap_or_default();
+
+        both.sort_unstable();
+
+        let forward = matching_origins(&self.nodes, self.directed, pattern)
+            .into_iter()
+            .chain(matching_origins(&self.nodes, self.mirrored, pattern))
+            .map(|(row, col, dir)| Match::from_segment(self.shape, (row, col), dir, len, false));
+
+        let backward = matching_origins(&self.nodes, self.mirrored, &reversed)
+            .into_iter()
+            .filter(|origin| both.binary_search(origin).is_err())
+            .map(|(row, col, dir)| Match::from_segment(self.shape, (row, col), dir, len, true));
+
+        forward.chain(backward).collect()
     }
 }
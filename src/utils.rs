@@ -1,5 +1,7 @@
 use std::{borrow::Cow, cmp::Ordering};
 
+use crate::{CellChange, Direction, DirectionSet, Shape};
+
 pub fn is_palindrome(word: &[u8]) -> bool {
     let len = word.len() / 2;
     word.iter().take(len).eq(word.iter().rev().take(len))
@@ -56,3 +58,75 @@ pub fn many_iter_eq<T: PartialEq, const N: usize>(
 
     eqs
 }
+
+/// Cells starting the lines of up to `len(axis)` cells that pass through one of the changed cells,
+/// where lines are read along the directions `directions` orients their axes in.
+pub fn lines_through(
+    shape: Shape,
+    directions: DirectionSet,
+    changes: &[CellChange],
+    len: impl Fn(Direction) -> usize,
+) -> Vec<(usize, usize)> {
+    let mut starts = vec![];
+
+    for change in changes {
+        for axis in directions.axes() {
+            let back = directions.orient(axis).reverse();
+
+            starts.extend(
+                (0..len(axis)).map_while(|k| shape.shift_point((change.row, change.col), back, k)),
+            );
+        }
+    }
+
+    starts.sort_unstable();
+    starts.dedup();
+
+    starts
+}
+
+pub fn gcd(a: usize, b: usize) -> usize {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+pub fn lcm(a: usize, b: usize) -> usize {
+    a / gcd(a, b) * b
+}
+
+/// Builds an index by inserting every row of a grid into a shard started by `empty`. With the
+/// `parallel` feature, every thread fills shards of its own, which are merged at the end.
+pub fn build_sharded<T: Send>(
+    rows: usize,
+    empty: impl Fn() -> T + Sync + Send,
+    insert_row: impl Fn(&mut T, usize) + Sync + Send,
+    merge: impl Fn(T, T) -> T + Sync + Send,
+) -> T {
+    #[cfg(feature = "parallel")]
+    {
+        use rayon::prelude::*;
+
+        (0..rows)
+            .into_par_iter()
+            .fold(&empty, |mut shard, row| {
+                insert_row(&mut shard, row);
+                shard
+            })
+            .reduce(&empty, merge)
+    }
+
+    #[cfg(not(feature = "parallel"))]
+    {
+        let _ = merge;
+        let mut shard = empty();
+
+        for row in 0..rows {
+            insert_row(&mut shard, row);
+        }
+
+        shard
+    }
+}
@@ -1,42 +1,934 @@
 use core::str;
-use std::{fs::File, io::BufReader};
+use std::{fs::File, io::BufReader, sync::Arc, thread};
 
-use crosswords::{Crossword, CrosswordHashMap, CrosswordNeedleSearch, NaiveSolver, Solver, Trie};
+use crosswords::{
+    build_solver, BoggleSolver, CompactTrie, Crossword, CrosswordAhoCorasick, CrosswordBitsets,
+    CrosswordDawg, CrosswordFmIndex, CrosswordHashMap, CrosswordNeedleSearch, Dictionary,
+    Direction, DirectionSet, EstimateSize, NaiveBoggleSolver, NaiveSolver, Normalizer, PathSolver,
+    Pattern, PatternSolver, SimdSolver, Solver, StreamingCounter, Trie, UpdatableSolver, SOLVERS,
+};
 
-#[test]
-fn solver_output_matches() {
-    let crossword = Crossword::parse(BufReader::new(File::open("test_4k.txt").unwrap())).unwrap();
-    let words = include_str!("../words.txt")
+fn load_crossword() -> Crossword {
+    Crossword::parse(BufReader::new(File::open("test_4k.txt").unwrap())).unwrap()
+}
+
+fn load_words() -> Vec<Vec<u8>> {
+    include_str!("../words.txt")
         .split('\n')
         .filter(|w| !w.is_empty())
-        .map(|w| w.as_bytes())
-        .collect::<Vec<_>>();
+        .map(|w| w.as_bytes().to_vec())
+        .chain((b'a'..=b'z').map(|ch| vec![ch]))
+        .collect()
+}
 
-    let naive = NaiveSolver::new(&crossword);
-    let mut solvers: Vec<Box<dyn Solver>> = vec![
-        Box::new(CrosswordNeedleSearch::new(&crossword)),
-        Box::new(Trie::new(&crossword, None)),
+fn build_solvers(
+    crossword: &Crossword,
+    directions: DirectionSet,
+) -> Vec<(String, Box<dyn Solver + '_>)> {
+    let mut solvers: Vec<(String, Box<dyn Solver>)> = vec![
+        (
+            "needle".into(),
+            Box::new(CrosswordNeedleSearch::with_directions(
+                crossword, directions,
+            )),
+        ),
+        (
+            "aho-corasick".into(),
+            Box::new(CrosswordAhoCorasick::with_directions(crossword, directions)),
+        ),
+        (
+            "fm-index".into(),
+            Box::new(CrosswordFmIndex::with_directions(crossword, directions)),
+        ),
+        (
+            "simd".into(),
+            Box::new(SimdSolver::with_directions(crossword, directions)),
+        ),
+        (
+            "bitsets".into(),
+            Box::new(CrosswordBitsets::with_directions(crossword, directions)),
+        ),
+        (
+            "dawg".into(),
+            Box::new(CrosswordDawg::with_directions(crossword, directions)),
+        ),
+        (
+            "trie".into(),
+            Box::new(Trie::with_directions(crossword, None, directions)),
+        ),
+        (
+            "compact trie".into(),
+            Box::new(CompactTrie::with_directions(crossword, None, directions)),
+        ),
     ];
 
-    (1..=8).for_each(|i| solvers.push(Box::new(CrosswordHashMap::<'_>::new(&crossword, i))));
+    (1..=8).for_each(|i| {
+        solvers.push((
+            format!("hash{i}"),
+            Box::new(CrosswordHashMap::<'_>::with_directions(
+                crossword, i, directions,
+            )),
+        ))
+    });
+
+    solvers
+}
+
+/// Checks that every solver finds the matches of `words` the naive solver does along `directions`,
+/// which are read back from the grid first.
+fn assert_solvers_match<W: AsRef<[u8]>>(
+    crossword: &Crossword,
+    directions: DirectionSet,
+    words: impl IntoIterator<Item = W>,
+) {
+    let naive = NaiveSolver::with_directions(crossword, directions);
+    let solvers = build_solvers(crossword, directions);
+    let folding = crossword.folding();
 
     for word in words {
+        let word = word.as_ref();
+        let decoded = crossword.charset.decode(word.iter().copied());
+
+        let mut expected = naive.find_occurrences(word);
+        expected.sort_unstable();
+
+        for m in &expected {
+            let found = crossword.get_word(m.row, m.col, m.dir, m.len).unwrap();
+
+            assert!(directions.contains(m.dir));
+            assert!(found
+                .map(|code| folding.fold(code))
+                .eq(word.iter().map(|&code| folding.fold(code))));
+        }
+
+        for (name, solver) in &solvers {
+            let mut found = solver.find_occurrences(word);
+            found.sort_unstable();
+
+            assert_eq!(
+                expected.len(),
+                solver.count_occurrences(word),
+                "occurrences of '{decoded}' in {directions} should match, mismatch for {name}",
+            );
+
+            assert_eq!(
+                expected, found,
+                "matches of '{decoded}' in {directions} should agree, mismatch for {name}",
+            );
+        }
+    }
+}
+
+#[test]
+fn solver_output_matches() {
+    let crossword = load_crossword();
+    let words = load_words();
+
+    let naive = NaiveSolver::new(&crossword);
+    let solvers = build_solvers(&crossword, DirectionSet::ALL);
+
+    for word in &words {
         let naive_count = naive.count_occurrences(word);
 
-        for (idx, solver) in solvers.iter().enumerate() {
+        for (name, solver) in &solvers {
             let count = solver.count_occurrences(word);
 
             assert_eq!(
                 naive_count,
                 count,
-                "occurrences of '{}' should match across solvers, mismatch for {}",
+                "occurrences of '{}' should match across solvers, mismatch for {name}",
+                unsafe { str::from_utf8_unchecked(word) },
+            );
+        }
+    }
+}
+
+#[test]
+fn batched_counts_match() {
+    let crossword = load_crossword();
+    let words = load_words();
+    // Repeated words have to be counted for each of their copies, and empty ones never occur
+    let words = words
+        .iter()
+        .chain(words.iter().take(10))
+        .map(|w| w.as_slice())
+        .chain([&b""[..]])
+        .collect::<Vec<_>>();
+
+    for directions in [
+        DirectionSet::ALL,
+        DirectionSet::from_slice(&[Direction::Left]),
+    ] {
+        let naive = NaiveSolver::with_directions(&crossword, directions);
+        let expected = naive.count_many(&words);
+
+        for (name, solver) in build_solvers(&crossword, directions) {
+            let counts = solver.count_many(&words);
+
+            for ((word, expected), count) in words.iter().zip(&expected).zip(counts) {
+                assert_eq!(
+                    *expected,
+                    count,
+                    "batched occurrences of '{}' in {directions} should match, mismatch for {name}",
+                    unsafe { str::from_utf8_unchecked(word) },
+                );
+            }
+        }
+    }
+}
+
+#[cfg(feature = "parallel")]
+#[test]
+fn parallel_counts_match() {
+    use crosswords::ParallelSolver;
+
+    let crossword = load_crossword();
+    let words = load_words();
+    let words = words.iter().map(|w| w.as_slice()).collect::<Vec<_>>();
+
+    let expected = NaiveSolver::new(&crossword).count_many(&words);
+    let solvers: [(&str, Box<dyn Solver + Sync>); 3] = [
+        ("needle", Box::new(CrosswordNeedleSearch::new(&crossword))),
+        ("trie", Box::new(Trie::new(&crossword, None))),
+        (
+            "hash4",
+            Box::new(CrosswordHashMap::<'_>::new(&crossword, 4)),
+        ),
+    ];
+
+    for (name, solver) in &solvers {
+        assert_eq!(
+            expected,
+            solver.par_count_many(&words),
+            "parallel batched occurrences should match, mismatch for {name}"
+        );
+    }
+}
+
+#[test]
+fn saved_indexes_match() {
+    use Direction::*;
+
+    let crossword = load_crossword().with_wrapping(true);
+    let words = load_words();
+    let words = words.iter().map(|w| w.as_slice()).collect::<Vec<_>>();
+    let directions = DirectionSet::from_slice(&[Right, Up, Diagonal, UpLeft]);
+
+    let expected = NaiveSolver::with_directions(&crossword, directions).count_many(&words);
+
+    let mut trie = vec![];
+    Trie::with_directions(&crossword, Some(14), directions)
+        .save(&mut trie)
+        .unwrap();
+    let mut hash = vec![];
+    CrosswordHashMap::with_directions(&crossword, 4, directions)
+        .save(&mut hash)
+        .unwrap();
+
+    let loaded: [(&str, Box<dyn Solver>); 2] = [
+        ("trie", Box::new(Trie::load(&trie[..], &crossword).unwrap())),
+        (
+            "hash4",
+            Box::new(CrosswordHashMap::load(&hash[..], &crossword).unwrap()),
+        ),
+    ];
+
+    for (name, solver) in &loaded {
+        assert_eq!(
+            expected,
+            solver.count_many(&words),
+            "occurrences should match after loading, mismatch for {name}"
+        );
+    }
+
+    // Indexes are only loaded for the grid they were built from
+    let mut other = crossword.clone();
+    other.set_word(0, 0, Right, b"zz".iter().copied());
+
+    assert!(Trie::load(&trie[..], &other).is_err());
+    assert!(Trie::load(&trie[..], &crossword.clone().with_wrapping(false)).is_err());
+    assert!(CrosswordHashMap::load(&hash[..], &other).is_err());
+    assert!(CrosswordHashMap::load(&trie[..], &crossword).is_err());
+    assert!(Trie::load(&trie[..trie.len() - 1], &crossword).is_err());
+}
+
+#[test]
+fn corrupt_indexes_fail() {
+    let crossword = Crossword::new(4, b"abcabcbaccbacabb".to_vec().into_boxed_slice());
+
+    let mut trie = vec![];
+    Trie::new(&crossword, None).save(&mut trie).unwrap();
+    let mut hash = vec![];
+    CrosswordHashMap::new(&crossword, 2)
+        .save(&mut hash)
+        .unwrap();
+
+    // Damaged files either fail to load or are searched without panicking
+    for (idx, mask) in (0..trie.len()).flat_map(|idx| [(idx, 0x01), (idx, 0x80), (idx, 0xff)]) {
+        let mut corrupt = trie.clone();
+        corrupt[idx] ^= mask;
+        if let Ok(solver) = Trie::load(&corrupt[..], &crossword) {
+            solver.find_occurrences(b"abcab");
+        }
+    }
+
+    for (idx, mask) in (0..hash.len()).flat_map(|idx| [(idx, 0x01), (idx, 0x80), (idx, 0xff)]) {
+        let mut corrupt = hash.clone();
+        corrupt[idx] ^= mask;
+        if let Ok(solver) = CrosswordHashMap::load(&corrupt[..], &crossword) {
+            solver.find_occurrences(b"abcab");
+        }
+    }
+
+    // The roots end the file
+    let len = trie.len();
+    trie[len - 4..].copy_from_slice(&u32::MAX.to_le_bytes());
+    assert!(Trie::load(&trie[..], &crossword).is_err());
+}
+
+#[test]
+fn mapped_needle_matches() {
+    use Direction::*;
+
+    let words = load_words();
+    let path = std::env::temp_dir().join(format!("mapped-needle-{}.idx", std::process::id()));
+
+    for (crossword, directions) in [
+        (load_crossword(), DirectionSet::ALL),
+        (
+            load_crossword().with_wrapping(true),
+            DirectionSet::from_slice(&[Left, Diagonal, UpRight]),
+        ),
+    ] {
+        let needle = CrosswordNeedleSearch::with_directions(&crossword, directions);
+        needle.save(File::create(&path).unwrap()).unwrap();
+
+        let mapped = CrosswordNeedleSearch::map(&path, &crossword).unwrap();
+        assert!(mapped.estimate_mapped_size() > 0);
+        assert!(mapped.estimate_size() < needle.estimate_size());
+        assert_eq!(
+            needle.estimate_mapped(),
+            (mapped.estimate_size(), mapped.estimate_mapped_size())
+        );
+
+        for word in words.iter().step_by(3) {
+            let mut expected = needle.find_occurrences(word);
+            expected.sort_unstable();
+
+            let mut found = mapped.find_occurrences(word);
+            found.sort_unstable();
+
+            assert_eq!(
+                expected,
+                found,
+                "mapped matches of '{}' in {directions} should agree",
                 unsafe { str::from_utf8_unchecked(word) },
-                match idx {
-                    0 => "needle".into(),
-                    1 => "trie".into(),
-                    v => format!("hash{}", v - 1),
+            );
+        }
+    }
+
+    // The last index saved is of the wrapping grid
+    assert!(CrosswordNeedleSearch::map(&path, &load_crossword()).is_err());
+
+    // Lines have to end where they do in the grid
+    let mut bytes = std::fs::read(&path).unwrap();
+    *bytes.last_mut().unwrap() = b'a';
+    std::fs::write(&path, &bytes).unwrap();
+    assert!(CrosswordNeedleSearch::map(&path, &load_crossword().with_wrapping(true)).is_err());
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn streamed_counts_match() {
+    use Direction::*;
+
+    let crossword = load_crossword();
+    let words = load_words();
+    let queries = words
+        .iter()
+        .map(|w| str::from_utf8(w).unwrap())
+        .collect::<Vec<_>>();
+    let words = words.iter().map(|w| w.as_slice()).collect::<Vec<_>>();
+    let grid = std::fs::read_to_string("test_4k.txt").unwrap();
+
+    for directions in [
+        DirectionSet::ALL,
+        DirectionSet::from_slice(&[Left]),
+        DirectionSet::from_slice(&[Up, UpRight, Diagonal]),
+    ] {
+        let expected = NaiveSolver::with_directions(&crossword, directions).count_many(&words);
+        let counter = StreamingCounter::new(&queries, directions, Normalizer::EXACT).unwrap();
+
+        assert_eq!(
+            expected,
+            counter.count(grid.as_bytes()).unwrap(),
+            "streamed occurrences in {directions} should match"
+        );
+    }
+
+    // Symbols are folded as they're read, the same as in solvers
+    let shouted = StreamingCounter::new(
+        &queries,
+        DirectionSet::ALL,
+        Normalizer {
+            fold_case: true,
+            fold_diacritics: false,
+        },
+    )
+    .unwrap();
+
+    assert_eq!(
+        NaiveSolver::new(&crossword).count_many(&words),
+        shouted.count(grid.to_uppercase().as_bytes()).unwrap()
+    );
+}
+
+#[test]
+fn updated_indexes_match() {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+    use Direction::*;
+
+    let words = load_words();
+    let queries = words.iter().map(|w| w.as_slice()).collect::<Vec<_>>();
+    let rng = &mut StdRng::seed_from_u64(23);
+
+    for (crossword, directions) in [
+        (load_crossword(), DirectionSet::ALL),
+        (
+            load_crossword().with_wrapping(true),
+            DirectionSet::from_slice(&[Left, Down, UpRight]),
+        ),
+    ] {
+        let mut crossword = Arc::new(crossword);
+
+        // Capped tries only know words up to their limit
+        let mut solvers: [(&str, usize, Box<dyn UpdatableSolver>); 3] = [
+            (
+                "trie",
+                usize::MAX,
+                Box::new(Trie::with_directions(&crossword, None, directions)),
+            ),
+            (
+                "trie capped to 5",
+                5,
+                Box::new(Trie::with_directions(&crossword, Some(5), directions)),
+            ),
+            (
+                "hash4",
+                usize::MAX,
+                Box::new(CrosswordHashMap::with_directions(
+                    crossword.clone(),
+                    4,
+                    directions,
+                )),
+            ),
+        ];
+
+        // Words are written over each other, some of them with a symbol the grid didn't have
+        for round in 0..12 {
+            let mut word = words[rng.gen_range(0..words.len())].clone();
+
+            if round % 4 == 3 {
+                word[0] = b'#';
+            }
+
+            let changes = loop {
+                let dir = Direction::ALL[rng.gen_range(0..8)];
+                let (row, col) = (
+                    rng.gen_range(0..crossword.rows()),
+                    rng.gen_range(0..crossword.cols()),
+                );
+
+                let edited = Arc::make_mut(&mut crossword);
+
+                if let Some(changes) = edited.replace_word(row, col, dir, word.iter().copied()) {
+                    break changes;
                 }
+            };
+
+            for (_, _, solver) in &mut solvers {
+                solver.update(&crossword, &changes);
+            }
+
+            // The hash map reads the edited grid instead of a copy of its own
+            assert_eq!(Arc::strong_count(&crossword), 2);
+
+            let expected = NaiveSolver::with_directions(&*crossword, directions);
+            let rebuilt = Trie::with_directions(&crossword, None, directions);
+
+            for (name, max_len, solver) in &solvers {
+                let queries = queries
+                    .iter()
+                    .copied()
+                    .filter(|word| word.len() <= *max_len)
+                    .collect::<Vec<_>>();
+
+                assert_eq!(
+                    rebuilt.count_many(&queries),
+                    solver.count_many(&queries),
+                    "updated occurrences in {directions} should match, mismatch for {name}"
+                );
+
+                for word in queries.iter().step_by(11).chain([&&word[..]]) {
+                    if word.len() > *max_len {
+                        continue;
+                    }
+
+                    let mut expected = expected.find_occurrences(word);
+                    expected.sort_unstable();
+
+                    let mut found = solver.find_occurrences(word);
+                    found.sort_unstable();
+
+                    assert_eq!(
+                        expected,
+                        found,
+                        "updated matches of '{}' in {directions} should agree, mismatch for {name}",
+                        unsafe { str::from_utf8_unchecked(word) },
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn repeated_updates_stay_bounded() {
+    use Direction::*;
+
+    let text = std::fs::read_to_string("test_4k.txt").unwrap();
+    let text = text
+        .lines()
+        .take(32)
+        .map(|line| &line[..32])
+        .collect::<Vec<_>>();
+    let mut crossword = Arc::new(Crossword::parse(text.join("\n").as_bytes()).unwrap());
+    let mut trie = Trie::new(&crossword, None);
+
+    let original = crossword
+        .get_word(7, 3, Diagonal, 9)
+        .unwrap()
+        .collect::<Vec<_>>();
+    let words = load_words();
+    let mut sizes = vec![];
+
+    // Nodes left behind by overwritten lines are reused by the next edits instead of piling up
+    for word in words.iter().filter(|word| word.len() == 9).take(40) {
+        for word in [word, &original] {
+            let edited = Arc::make_mut(&mut crossword);
+            let changes = edited
+                .replace_word(7, 3, Diagonal, word.iter().copied())
+                .unwrap();
+
+            trie.update(&crossword, &changes);
+        }
+
+        sizes.push(trie.estimate_size());
+    }
+
+    assert!(sizes.len() > 1);
+    assert!(
+        sizes.iter().all(|&size| size <= sizes[0] + sizes[0] / 20),
+        "restored tries should stay about their size, got {sizes:?}"
+    );
+
+    let queries = words.iter().map(Vec::as_slice).collect::<Vec<_>>();
+    assert_eq!(
+        Trie::new(&crossword, None).count_many(&queries),
+        trie.count_many(&queries)
+    );
+}
+
+#[test]
+fn shared_solvers_match() {
+    use Direction::*;
+
+    let crossword = Arc::new(load_crossword().with_wrapping(true));
+    let words = Arc::new(load_words());
+    let directions = DirectionSet::from_slice(&[Right, Down, AntiDiagonal, UpLeft]);
+
+    let queries = words.iter().map(|w| w.as_slice()).collect::<Vec<_>>();
+    let expected = NaiveSolver::with_directions(&*crossword, directions).count_many(&queries);
+
+    // Solvers holding the grid outlive the scope they were built in, on threads of their own
+    let handles = SOLVERS
+        .iter()
+        .map(|entry| entry.name)
+        .chain(["trie:14", "compact-trie:14", "hash:2", "hash:7"])
+        .map(|name| {
+            let solver = build_solver(name, &crossword, directions).unwrap();
+            let words = Arc::clone(&words);
+
+            thread::spawn(move || {
+                let queries = words.iter().map(|w| w.as_slice()).collect::<Vec<_>>();
+                (name, solver.count_many(&queries))
+            })
+        })
+        .collect::<Vec<_>>();
+
+    for handle in handles {
+        let (name, counts) = handle.join().unwrap();
+
+        assert_eq!(
+            expected, counts,
+            "occurrences should match, mismatch for {name}"
+        );
+    }
+
+    assert!(build_solver("bogus", &crossword, directions).is_err());
+}
+
+#[test]
+fn solver_matches_agree() {
+    let crossword = load_crossword();
+    let words = load_words();
+
+    assert_eq!(NaiveSolver::new(&crossword).find_occurrences(b""), []);
+    assert_eq!(
+        crossword.get_word(0, 0, Direction::Right, 0).map(|_| ()),
+        None
+    );
+
+    assert_solvers_match(
+        &crossword,
+        DirectionSet::ALL,
+        words.iter().map(Vec::as_slice).chain([&b""[..]]),
+    );
+}
+
+#[test]
+fn restricted_directions_match() {
+    use Direction::*;
+
+    let crossword = load_crossword();
+    let words = load_words();
+
+    for directions in [
+        DirectionSet::FORWARD,
+        DirectionSet::from_slice(&[Right, Down]),
+        DirectionSet::from_slice(&[Left]),
+        DirectionSet::from_slice(&[Down, Left, Right]),
+        DirectionSet::from_slice(&[Up, UpRight, Diagonal]),
+    ] {
+        assert_solvers_match(&crossword, directions, words.iter().step_by(7));
+    }
+}
+
+#[test]
+fn pattern_queries_match() {
+    use Direction::*;
+
+    let crossword = load_crossword();
+    let patterns = [
+        "?",
+        "[aeiou]",
+        "c?t",
+        "??e",
+        "[^a-m]a?",
+        "s?[aeiou]?",
+        "a??a",
+        "?[xyz]?",
+        "t*",
+        "ca*",
+        "[^a-m]e*",
+        "?[xyz]*",
+    ]
+    .into_iter()
+    .map(|p| p.parse::<Pattern>().unwrap())
+    // Patterns derived from words, with every other letter left open
+    .chain(load_words().iter().step_by(13).map(|word| {
+        word.iter()
+            .enumerate()
+            .map(|(idx, &ch)| if idx % 2 == 1 { '?' } else { ch as char })
+            .collect::<String>()
+            .parse()
+            .unwrap()
+    }))
+    .collect::<Vec<_>>();
+
+    for directions in [
+        DirectionSet::ALL,
+        DirectionSet::from_slice(&[Down, Left, Right]),
+        DirectionSet::from_slice(&[Up, UpRight]),
+    ] {
+        let naive = NaiveSolver::with_directions(&crossword, directions);
+        let solvers: [(&str, Box<dyn PatternSolver>); 2] = [
+            (
+                "trie",
+                Box::new(Trie::with_directions(&crossword, None, directions)),
+            ),
+            (
+                "compact trie",
+                Box::new(CompactTrie::with_directions(&crossword, None, directions)),
+            ),
+        ];
+
+        for pattern in &patterns {
+            let mut expected = naive.find_pattern(pattern);
+            expected.sort_unstable();
+
+            for m in &expected {
+                let found = crossword.get_word(m.row, m.col, m.dir, m.len).unwrap();
+                assert!(pattern.matches(found));
+            }
+
+            for (name, solver) in &solvers {
+                let mut found = solver.find_pattern(pattern);
+                found.sort_unstable();
+
+                assert_eq!(
+                    expected.len(),
+                    solver.count_pattern(pattern),
+                    "occurrences of {pattern:?} in {directions} should match, mismatch for {name}"
+                );
+                assert_eq!(
+                    expected, found,
+                    "matches of {pattern:?} in {directions} should agree, mismatch for {name}"
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn regex_queries_match() {
+    use Direction::*;
+
+    let crossword = load_crossword();
+    // Words overlapping with themselves could be matched fewer times, as regex matches within a
+    // line don't overlap
+    let words = load_words()
+        .into_iter()
+        .filter(|word| (1..word.len()).all(|len| word[..len] != word[word.len() - len..]))
+        .step_by(5)
+        .collect::<Vec<_>>();
+
+    for directions in [
+        DirectionSet::ALL,
+        DirectionSet::from_slice(&[Down, Left, Right]),
+    ] {
+        let naive = NaiveSolver::with_directions(&crossword, directions);
+        let needle = CrosswordNeedleSearch::with_directions(&crossword, directions);
+
+        for word in &words {
+            let word_str = str::from_utf8(word).unwrap();
+            let regex = regex::bytes::Regex::new(word_str).unwrap();
+
+            let mut expected = naive.find_occurrences(word);
+            expected.sort_unstable();
+
+            let mut found = needle.find_regex(&regex);
+            found.sort_unstable();
+
+            assert_eq!(
+                expected, found,
+                "regex matches of '{word_str}' in {directions} should agree"
             );
         }
     }
 }
+
+#[test]
+fn dictionary_scan_matches() {
+    use Direction::*;
+
+    let crossword = load_crossword();
+    let words = load_words();
+    let dictionary = Dictionary::new(&words);
+
+    for directions in [
+        DirectionSet::ALL,
+        DirectionSet::from_slice(&[Down, Left, Right]),
+        DirectionSet::from_slice(&[UpLeft]),
+    ] {
+        let naive = NaiveSolver::with_directions(&crossword, directions);
+
+        let mut expected = words
+            .iter()
+            .flat_map(|word| naive.find_occurrences(word))
+            .collect::<Vec<_>>();
+        expected.sort_unstable();
+        expected.dedup();
+
+        let mut found = dictionary.scan(&crossword, directions);
+        found.sort_unstable();
+
+        assert_eq!(
+            expected, found,
+            "dictionary words in {directions} should agree"
+        );
+    }
+}
+
+#[test]
+fn boggle_paths_match() {
+    let crossword = load_crossword();
+    let words = load_words();
+
+    let naive = NaiveBoggleSolver::new(&crossword);
+    let boggle = BoggleSolver::new(&crossword);
+
+    for word in words.iter().step_by(3) {
+        let mut expected = naive.find_paths(word);
+        expected.sort_unstable();
+
+        for path in &expected {
+            let letters = path.iter().map(|&(row, col)| crossword.get(row, col));
+            assert!(letters.eq(word.iter().copied()));
+
+            for pair in path.windows(2) {
+                assert!(pair[0].0.abs_diff(pair[1].0) <= 1 && pair[0].1.abs_diff(pair[1].1) <= 1);
+            }
+        }
+
+        let mut found = boggle.find_paths(word);
+        found.sort_unstable();
+
+        assert_eq!(
+            expected.len(),
+            boggle.count_paths(word),
+            "path occurrences of '{}' should match",
+            unsafe { str::from_utf8_unchecked(word) },
+        );
+        assert_eq!(expected, found, "paths of '{}' should agree", unsafe {
+            str::from_utf8_unchecked(word)
+        },);
+    }
+}
+
+#[test]
+fn wrapping_grids_match() {
+    use Direction::*;
+
+    let crossword = load_crossword();
+    let words = load_words();
+
+    // Rows and columns of different lengths make diagonals wrap over several rows, and rows longer
+    // than a word of bits wrap over padded bitsets
+    for (rows, cols) in [(12, 18), (14, 70)] {
+        let cells = crossword.get_rows().flatten().take(rows * cols);
+        let cropped = Crossword::new(rows, cells.copied().collect()).with_wrapping(true);
+
+        for directions in [
+            DirectionSet::ALL,
+            DirectionSet::from_slice(&[Left, Diagonal, UpRight]),
+        ] {
+            assert_solvers_match(&cropped, directions, words.iter().step_by(3));
+        }
+    }
+}
+
+#[test]
+fn unicode_grids_match() {
+    let accented = |text: &str| {
+        text.chars()
+            .map(|ch| match ch {
+                'a' => 'á',
+                'c' => 'č',
+                'e' => 'ě',
+                'r' => 'ř',
+                's' => 'š',
+                'u' => 'ů',
+                'z' => 'ž',
+                ch => ch,
+            })
+            .collect::<String>()
+    };
+
+    let crossword = load_crossword();
+    let czech =
+        Crossword::parse(accented(&std::fs::read_to_string("test_4k.txt").unwrap()).as_bytes())
+            .unwrap();
+
+    assert_eq!(czech.rows(), crossword.rows());
+    assert_eq!(czech.cols(), crossword.cols());
+
+    let words = load_words();
+    let queries = words
+        .iter()
+        .step_by(5)
+        .map(|word| {
+            czech
+                .encode(&accented(str::from_utf8(word).unwrap()))
+                .unwrap()
+        })
+        .collect::<Vec<_>>();
+
+    // Accented grids have as many occurrences as the plain one
+    let (naive, accented_naive) = (NaiveSolver::new(&crossword), NaiveSolver::new(&czech));
+
+    for (word, query) in words.iter().step_by(5).zip(&queries) {
+        assert_eq!(
+            naive.count_occurrences(word),
+            accented_naive.count_occurrences(query)
+        );
+    }
+
+    assert_solvers_match(&czech, DirectionSet::ALL, &queries);
+}
+
+#[test]
+fn reserved_symbols_match() {
+    // Dots are stored apart from the bytes line plans are separated with
+    let crossword = Crossword::parse("ab.\n.cd\nxyz\n".as_bytes()).unwrap();
+    let naive = NaiveSolver::new(&crossword);
+
+    let words =
+        [".", "..", "b.", ".c", "b...c", "ab.", "d.x"].map(|word| crossword.encode(word).unwrap());
+
+    assert_eq!(naive.count_occurrences(&words[0]), 2);
+    assert_eq!(naive.count_occurrences(&words[2]), 2);
+
+    assert_solvers_match(&crossword, DirectionSet::ALL, &words);
+}
+
+#[test]
+fn normalized_queries_match() {
+    // Every other row is uppercased and vowels get accents, which folding has to undo
+    let text = std::fs::read_to_string("test_4k.txt")
+        .unwrap()
+        .lines()
+        .enumerate()
+        .map(|(idx, line)| {
+            line.chars()
+                .map(|ch| match ch {
+                    'a' => 'á',
+                    'e' => 'ê',
+                    'o' => 'ö',
+                    ch if idx % 2 == 1 => ch.to_ascii_uppercase(),
+                    ch => ch,
+                })
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let crossword = load_crossword();
+    let folded = Crossword::parse(text.as_bytes())
+        .unwrap()
+        .with_normalizer(Normalizer::LOOSE);
+
+    let words = load_words();
+    let (naive, folded_naive) = (NaiveSolver::new(&crossword), NaiveSolver::new(&folded));
+    let mut queries = vec![];
+
+    for word in words.iter().step_by(7) {
+        let word = str::from_utf8(word).unwrap();
+        let expected = naive.count_occurrences(word.as_bytes());
+
+        for query in [
+            word.to_string(),
+            word.to_uppercase(),
+            word.replace('e', "é"),
+        ] {
+            let encoded = folded.encode(&query).unwrap();
+
+            // Folded grids have as many occurrences as the plain one
+            assert_eq!(expected, folded_naive.count_occurrences(&encoded));
+            queries.push(encoded);
+        }
+    }
+
+    assert_solvers_match(&folded, DirectionSet::ALL, &queries);
+}